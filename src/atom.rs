@@ -0,0 +1,233 @@
+//! Interned strings backed by a static set.
+//!
+//! An `Atom` is a single word referring either to a value of a static `PhfSet`
+//! or to a reference counted string interned at run time, so atoms can be
+//! compared and hashed without looking at their contents. Common strings are
+//! listed in the static set and never allocate.
+//!
+//! The static set is chosen by a type implementing `AtomSet`, which is
+//! declared with `phf_atom_set!` for a set declared with the item form of
+//! `phf_set!`. Atoms of the values of that set can then be created while
+//! compiling with `phf_atom!`, which takes the atom type and the set and fails
+//! to compile unless the set is the one declared for the type:
+//!
+//! ```rust
+//! # #![feature(phase)]
+//! extern crate phf;
+//! #[phase(syntax)]
+//! extern crate phf_mac;
+//!
+//! use phf::atom::Atom;
+//!
+//! phf_set! {
+//!     static HTML_ATOMS = {
+//!         "div",
+//!         "span",
+//!     };
+//! }
+//!
+//! struct Html;
+//!
+//! phf_atom_set!(Html, HTML_ATOMS)
+//!
+//! static DIV: Atom<Html> = phf_atom!(Html, HTML_ATOMS, "div");
+//!
+//! # fn main() {
+//! let div: Atom<Html> = Atom::from_slice("div");
+//! assert!(div == DIV);
+//! assert!(div != phf_atom!(Html, HTML_ATOMS, "span"));
+//! assert!(div.is_static());
+//!
+//! let custom: Atom<Html> = Atom::from_slice("my-element");
+//! assert!(custom == Atom::from_slice("my-element"));
+//! assert_eq!("my-element", custom.as_slice());
+//! # }
+//! ```
+
+use core::prelude::*;
+use core::fmt;
+use core::mem;
+use std::collections::HashMap;
+use std::container::MutableMap;
+use std::hash::{Hash, Writer};
+use std::owned::Box;
+use std::str::Str;
+use std::string::String;
+use std::sync::{Mutex, Once, ONCE_INIT};
+use std::sync::atomics::{AtomicUint, SeqCst};
+
+use PhfSet;
+
+/// A type selecting the static set of a kind of `Atom`.
+///
+/// Implement it with `phf_atom_set!` so that `phf_atom!` can check that its
+/// atoms are created from the right set.
+pub trait AtomSet {
+    /// Returns the set of strings stored statically.
+    ///
+    /// The argument is always `None`. It only selects the implementation.
+    fn static_atoms(_: Option<Self>) -> &'static PhfSet;
+}
+
+fn static_atoms<A: AtomSet>() -> &'static PhfSet {
+    AtomSet::static_atoms(None::<A>)
+}
+
+// Atoms with the low bit set hold the index of a value of the static set in the
+// remaining bits. The others hold the address of a `DynamicEntry`. `phf_atom!`
+// relies on this encoding.
+static STATIC_TAG: uint = 1;
+
+struct DynamicEntry {
+    string: String,
+    refs: AtomicUint,
+}
+
+// The strings interned at run time, mapped to the addresses of their entries.
+// The table is shared by every kind of atom.
+type DynamicTable = Mutex<HashMap<String, uint>>;
+
+static mut DYNAMIC_TABLE: *mut DynamicTable = 0 as *mut DynamicTable;
+static mut DYNAMIC_TABLE_INIT: Once = ONCE_INIT;
+
+fn dynamic_table() -> &'static DynamicTable {
+    unsafe {
+        DYNAMIC_TABLE_INIT.doit(|| {
+            let table: Box<DynamicTable> = box Mutex::new(HashMap::new());
+            DYNAMIC_TABLE = mem::transmute(table);
+        });
+        &*DYNAMIC_TABLE
+    }
+}
+
+// Returns the address of the entry for `s`, taking a reference to it.
+fn intern(s: &str) -> uint {
+    let mut table = dynamic_table().lock();
+    match table.find_equiv(&s) {
+        Some(&addr) => {
+            let entry = addr as *mut DynamicEntry;
+            unsafe { (*entry).refs.fetch_add(1, SeqCst); }
+            return addr;
+        }
+        None => {}
+    }
+
+    let entry = box DynamicEntry {
+        string: String::from_str(s),
+        refs: AtomicUint::new(1),
+    };
+    let addr: uint = unsafe { mem::transmute(entry) };
+    table.insert(String::from_str(s), addr);
+    addr
+}
+
+/// An interned string.
+///
+/// Two atoms of the same kind are equal exactly when their strings are, but
+/// comparing or hashing them only looks at a single word.
+///
+/// `Atom` does not implement `Deref<str>`, as `str` is unsized and can't be
+/// the target of `Deref`. Use `as_slice`, or pass the atom where a `Str` is
+/// expected.
+///
+/// # Note
+///
+/// The field of this struct is public so that static atoms may be created by
+/// the `phf_atom!` macro. It is subject to change at any time and should never
+/// be accessed directly.
+pub struct Atom<A> {
+    #[doc(hidden)]
+    pub data: uint,
+}
+
+impl<A> Atom<A> {
+    /// Returns true if the atom's string is a value of the static set.
+    #[inline]
+    pub fn is_static(&self) -> bool {
+        self.data & STATIC_TAG != 0
+    }
+
+    fn entry(&self) -> *mut DynamicEntry {
+        self.data as *mut DynamicEntry
+    }
+}
+
+impl<A: AtomSet> Atom<A> {
+    /// Returns the atom for `s`.
+    ///
+    /// Strings which are not values of the static set are interned in a table
+    /// shared between threads, and freed when their last atom is dropped.
+    pub fn from_slice(s: &str) -> Atom<A> {
+        match static_atoms::<A>().index_of(&s) {
+            Some(idx) => Atom { data: (idx << 1) | STATIC_TAG },
+            None => Atom { data: intern(s) },
+        }
+    }
+
+    /// Returns the atom's string.
+    pub fn as_slice<'a>(&'a self) -> &'a str {
+        if self.is_static() {
+            static_atoms::<A>().key_at(self.data >> 1)
+                               .expect("invalid static atom")
+        } else {
+            unsafe { (*self.entry()).string.as_slice() }
+        }
+    }
+}
+
+impl<A> Clone for Atom<A> {
+    fn clone(&self) -> Atom<A> {
+        if !self.is_static() {
+            unsafe { (*self.entry()).refs.fetch_add(1, SeqCst); }
+        }
+        Atom { data: self.data }
+    }
+}
+
+impl<A> Drop for Atom<A> {
+    fn drop(&mut self) {
+        if self.is_static() {
+            return;
+        }
+
+        // The count is only decremented with the table locked so that `intern`
+        // can't find an entry while it is being freed.
+        let mut table = dynamic_table().lock();
+        unsafe {
+            let entry = self.entry();
+            if (*entry).refs.fetch_sub(1, SeqCst) == 1 {
+                table.remove(&(*entry).string);
+                let _: Box<DynamicEntry> = mem::transmute(entry);
+            }
+        }
+    }
+}
+
+impl<A> PartialEq for Atom<A> {
+    #[inline]
+    fn eq(&self, other: &Atom<A>) -> bool {
+        self.data == other.data
+    }
+}
+
+impl<A> Eq for Atom<A> {}
+
+impl<A, S: Writer> Hash<S> for Atom<A> {
+    #[inline]
+    fn hash(&self, state: &mut S) {
+        self.data.hash(state)
+    }
+}
+
+impl<A: AtomSet> Str for Atom<A> {
+    #[inline]
+    fn as_slice<'a>(&'a self) -> &'a str {
+        self.as_slice()
+    }
+}
+
+impl<A: AtomSet> fmt::Show for Atom<A> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.as_slice())
+    }
+}
@@ -4,15 +4,2383 @@
 #![crate_type="rlib"]
 #![crate_type="dylib"]
 #![warn(missing_doc)]
+#![feature(phase)]
+#![no_std]
+
+#[phase(syntax, link)]
+extern crate core;
+#[cfg(feature = "std")]
+extern crate std;
+
+use core::prelude::*;
+use core::fmt;
+use core::slice;
+#[cfg(feature = "std")]
+use core::iter;
+#[cfg(feature = "std")]
+use core::mem;
+#[cfg(feature = "std")]
+use std::container::{Container, Map, Set};
+#[cfg(feature = "std")]
+use std::vec::Vec;
+
+mod sip;
+#[cfg(feature = "std")]
+mod suggest;
+
+#[cfg(feature = "std")]
+pub mod atom;
+
+// `deriving` refers to `::std`, which isn't linked without the `std` feature.
+#[cfg(not(feature = "std"))]
+mod std {
+    pub use core::{clone, cmp, fmt};
+}
+
+/// An immutable map constructed at compile time.
+///
+/// `PhfMap`s may be created with the `phf_map` macro:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::PhfMap;
+///
+/// static MY_MAP: PhfMap<int> = phf_map! {
+///    "hello" => 10,
+///    "world" => 11,
+/// };
+///
+/// # fn main() {}
+/// ```
+///
+/// The macro can also declare the static itself, inferring its type from the
+/// type of the values. Any number of maps may be declared in one invocation,
+/// and each may have attributes and doc comments:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// phf_map! {
+///     /// Some greetings.
+///     pub static GREETINGS: int = {
+///        "hello" => 10,
+///        "world" => 11,
+///     };
+///
+///     static FAREWELLS: int = {
+///        "goodbye" => 12,
+///     };
+/// }
+///
+/// # fn main() {}
+/// ```
+///
+/// The other macros accept the same form. The sets leave out the type:
+/// `static MY_SET = { "hello", "world" };`.
+///
+/// The value of a key in a map declared this way can be looked up while
+/// compiling with `phf_get!`. A missing key is a compile error, and no hashing
+/// is done at run time. The map must be declared earlier in the crate, outside
+/// of any function, and is named by its path from the current module, as for
+/// `#[phf_key]` statics:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// phf_map! {
+///     static GREETINGS: int = {
+///        "hello" => 10,
+///        "world" => 11,
+///     };
+/// }
+///
+/// fn main() {
+///     assert_eq!(&10, phf_get!(GREETINGS, "hello"));
+/// }
+/// ```
+///
+/// Several keys may share one value by separating them with `|`. The value is
+/// only stored once:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::PhfMap;
+///
+/// static EXTENSIONS: PhfMap<&'static str> = phf_map! {
+///    "jpg" | "jpeg" => "image/jpeg",
+///    "png" => "image/png",
+/// };
+///
+/// # fn main() {}
+/// ```
+///
+/// Keys may also name string statics marked with `#[phf_key]`, either on their
+/// own or as arguments of `concat!`. Macros are expanded before names are
+/// resolved, so the attribute is what makes the value of a static visible to
+/// `phf_map!`. The statics must be declared before the maps using them, and
+/// are found by their path from the current module, through `self`, `super` or
+/// child modules but not through `use` imports. Naming anything else is an
+/// error:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::PhfMap;
+///
+/// #[phf_key]
+/// static HELLO: &'static str = "hello";
+///
+/// static MY_MAP: PhfMap<int> = phf_map! {
+///    HELLO => 10,
+///    concat!(HELLO, " world") => 11,
+/// };
+///
+/// # fn main() {}
+/// ```
+///
+/// Entries may be conditionally compiled with `#[cfg]` attributes, in the
+/// same way as items:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::PhfMap;
+///
+/// static MY_MAP: PhfMap<int> = phf_map! {
+///    "hello" => 10,
+///    #[cfg(not(windows))]
+///    "world" => 11,
+///    #[cfg(windows)]
+///    "world" => 12,
+/// };
+///
+/// # fn main() {}
+/// ```
+///
+/// A key listed more than once is an error by default. The `on_duplicate`
+/// option, given as an inner attribute at the start of the invocation, can
+/// instead keep the `"first"` or `"last"` definition of each key:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::PhfMap;
+///
+/// static MY_MAP: PhfMap<int> = phf_map! {
+///    #![on_duplicate = "last"]
+///    "hello" => 10,
+///    "hello" => 11,
+/// };
+///
+/// # fn main() {}
+/// ```
+///
+/// In a `PhfOrderedMap` the entry that is kept stays in its own position.
+///
+/// A value may itself be a `phf_map!` or `phf_set!` invocation, or one of the
+/// other table macros. The inner table is stored as an anonymous static and
+/// the value is a reference to it. `find_path` and `contains_path` take a key
+/// of the outer map and a key of the inner table, and look both up in one
+/// call:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::PhfMap;
+///
+/// static ATTRIBUTES: PhfMap<&'static PhfMap<bool>> = phf_map! {
+///    "svg" => phf_map! {
+///        "viewBox" => true,
+///        "width" => false,
+///    },
+///    "img" => phf_map! {
+///        "src" => true,
+///    },
+/// };
+///
+/// fn main() {
+///     assert_eq!(Some(&true), ATTRIBUTES.find_path(&"svg", &"viewBox"));
+///     assert_eq!(None, ATTRIBUTES.find_path(&"img", &"viewBox"));
+/// }
+/// ```
+///
+/// `phf_match!` uses a map from patterns to arm numbers to pick one of several
+/// arms for a string, in the same way as a `match` expression. The patterns
+/// may use `|` and `#[cfg]` as above, and the `_` arm must be given last:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// fn precedence(op: &str) -> int {
+///     phf_match!(op,
+///         "*" | "/" | "%" => 2,
+///         "+" | "-" => 1,
+///         _ => 0,
+///     )
+/// }
+///
+/// fn main() {
+///     assert_eq!(2, precedence("%"));
+///     assert_eq!(0, precedence("="));
+/// }
+/// ```
+///
+/// `phf_enum!` declares a C-like enum along with a map from the spelling of
+/// each variant to the variant. The enum gets a `map` function returning the
+/// map, an `as_str` method returning the same string as `find_key`, and an
+/// implementation of `FromStr`:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// phf_enum! {
+///     #[deriving(PartialEq, Show)]
+///     pub enum Keyword {
+///         Loop = "loop",
+///         While = "while",
+///     }
+/// }
+///
+/// fn main() {
+///     assert_eq!(Some(Loop), from_str("loop"));
+///     assert_eq!(Some(&While), Keyword::map().find(&"while"));
+///     assert_eq!("while", While.as_str());
+/// }
+/// ```
+///
+/// `phf_table!` declares a static holding several columns of values over the
+/// same keys, along with its type. The keys are hashed once: `index_of` finds
+/// the position of a key's values, which is the same in every column, and
+/// each column has a method returning it. A column named `flags` also gets a
+/// `find_flags` method, which looks up the cell of a single key:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// phf_table! {
+///     static COMMANDS: Commands {
+///         name: &'static str,
+///         flags: uint,
+///     } = {
+///         "build" => ("Build", 1),
+///         "bench" => ("Bench", 3),
+///     };
+/// }
+///
+/// fn main() {
+///     let idx = COMMANDS.index_of(&"bench").unwrap();
+///     assert_eq!("Bench", COMMANDS.name()[idx]);
+///     assert_eq!(3, COMMANDS.flags()[idx]);
+///     assert_eq!(Some(&"Build"), COMMANDS.find_name(&"build"));
+///     assert_eq!(None, COMMANDS.find_flags(&"test"));
+/// }
+/// ```
+///
+/// # Note
+///
+/// The fields of this struct are public so that they may be initialized by the
+/// `phf_map` macro and by code generated with the `phf_codegen` crate. They are
+/// subject to change at any time and should never be accessed directly.
+pub struct PhfMap<T> {
+    #[doc(hidden)]
+    pub k1: u64,
+    #[doc(hidden)]
+    pub k2: u64,
+    #[doc(hidden)]
+    pub disps: &'static [(uint, uint)],
+    #[doc(hidden)]
+    pub entries: &'static [(&'static str, uint)],
+    #[doc(hidden)]
+    pub values: &'static [T],
+    #[doc(hidden)]
+    pub lens: &'static [uint],
+    #[doc(hidden)]
+    pub len_idxs: &'static [uint],
+    #[doc(hidden)]
+    pub len_buckets: &'static [(uint, uint)],
+}
+
+static LOG_MAX_SIZE: uint = 21;
+
+#[doc(hidden)]
+pub static MAX_SIZE: uint = 1 << LOG_MAX_SIZE;
+
+// The number of keys `find_many` resolves at a time.
+static BATCH_LEN: uint = 16;
+
+// `core::intrinsics` has no prefetch intrinsic, so the LLVM one is declared
+// by its link name. It is only declared when building against `std`, whose
+// targets are known to lower it. Elsewhere `prefetch` does nothing, and
+// `find_many` just doesn't overlap the loads of the keys of a batch.
+#[cfg(feature = "std")]
+extern {
+    #[link_name = "llvm.prefetch"]
+    fn llvm_prefetch(addr: *mut i8, rw: i32, locality: i32, cache_type: i32);
+}
+
+// Starts loading the cache line holding `value` ahead of a read.
+#[cfg(feature = "std")]
+#[inline]
+fn prefetch<T>(value: &T) {
+    unsafe { llvm_prefetch(mem::transmute(value), 0, 3, 1) }
+}
+
+#[cfg(not(feature = "std"))]
+#[inline]
+fn prefetch<T>(_: &T) {}
+
+// The body of every `find_many`. `slot_entry` takes a slot given by the
+// displacements to the index of its entry, and `value` takes the value index
+// of an entry to what is stored in `values`.
+fn find_many_batched<V>(k1: u64, k2: u64, disps: &[(uint, uint)], slots: uint,
+                        entries: &[(&'static str, uint)], keys: &[&str],
+                        values: &mut [Option<V>], slot_entry: |uint| -> uint,
+                        value: |uint| -> V) {
+    if keys.len() != values.len() {
+        fail!("find_many: {} keys but {} values", keys.len(), values.len());
+    }
+
+    if disps.is_empty() || slots == 0 {
+        for found in values.mut_iter() {
+            *found = None;
+        }
+        return;
+    }
+
+    let mut hashes = [(0u, 0u, 0u), ..BATCH_LEN];
+    let mut idxs = [0u, ..BATCH_LEN];
+    for (keys, values) in keys.chunks(BATCH_LEN)
+                              .zip(values.mut_chunks(BATCH_LEN)) {
+        for (h, key) in hashes.mut_iter().zip(keys.iter()) {
+            *h = hash(*key, k1, k2);
+            let (g, _, _) = *h;
+            prefetch(&disps[g % disps.len()]);
+        }
+
+        for (idx, &(g, f1, f2)) in idxs.mut_iter().zip(hashes.iter())
+                                        .take(keys.len()) {
+            let (d1, d2) = disps[g % disps.len()];
+            *idx = slot_entry(displace(f1, f2, d1, d2) % slots);
+            prefetch(&entries[*idx]);
+        }
+
+        for ((found, key), &idx) in values.mut_iter().zip(keys.iter())
+                                          .zip(idxs.iter()) {
+            let (s, value_idx) = entries[idx];
+            *found = if s == *key {
+                Some(value(value_idx))
+            } else {
+                None
+            };
+        }
+    }
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn sip_hash(k0: u64, k1: u64, bytes: &[u8]) -> u64 {
+    sip::hash(k0, k1, bytes)
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn hash(s: &str, k1: u64, k2: u64) -> (uint, uint, uint) {
+    hash_bytes(s.as_bytes(), k1, k2)
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn hash_bytes(bytes: &[u8], k1: u64, k2: u64) -> (uint, uint, uint) {
+    let hash = sip::hash(k1, k2, bytes);
+    let mask = (MAX_SIZE - 1) as u64;
+
+    ((hash & mask) as uint,
+     ((hash >> LOG_MAX_SIZE) & mask) as uint,
+     ((hash >> (2 * LOG_MAX_SIZE)) & mask) as uint)
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn displace(f1: uint, f2: uint, d1: uint, d2: uint) -> uint {
+    d2 + f1 * d1 + f2
+}
+
+/// A type which can be hashed for the tables of a perfect hash function.
+///
+/// Strings hash as their UTF-8 bytes. Integers, `char` and `bool` are widened
+/// to a `u64` and hash as its little endian bytes, so a value hashes the same
+/// way whatever its integer type.
+pub trait PhfHash {
+    /// Hashes the value with the keys `k1` and `k2`.
+    fn phf_hash(&self, k1: u64, k2: u64) -> (uint, uint, uint);
+}
+
+impl<'a> PhfHash for &'a str {
+    #[inline]
+    fn phf_hash(&self, k1: u64, k2: u64) -> (uint, uint, uint) {
+        hash(*self, k1, k2)
+    }
+}
+
+impl<'a> PhfHash for &'a [u8] {
+    #[inline]
+    fn phf_hash(&self, k1: u64, k2: u64) -> (uint, uint, uint) {
+        hash_bytes(*self, k1, k2)
+    }
+}
+
+macro_rules! widening_phf_hash(
+    ($($t:ty),+) => ($(
+        impl PhfHash for $t {
+            #[inline]
+            fn phf_hash(&self, k1: u64, k2: u64) -> (uint, uint, uint) {
+                let value = *self as u64;
+                let mut bytes = [0u8, ..8];
+                for i in range(0u, 8) {
+                    bytes[i] = (value >> (8 * i)) as u8;
+                }
+                hash_bytes(&bytes, k1, k2)
+            }
+        }
+    )+)
+)
+
+widening_phf_hash!(u8, u16, u32, u64, uint, i8, i16, i32, i64, int, char,
+                   bool)
+
+// Derives both SipHash keys from a seed with SplitMix64, so that nearby seeds
+// give unrelated keys.
+#[doc(hidden)]
+pub fn seed_keys(seed: u64) -> (u64, u64) {
+    fn next(state: &mut u64) -> u64 {
+        *state += 0x9e3779b97f4a7c15u64;
+        let mut z = *state;
+        z = (z ^ (z >> 30)) * 0xbf58476d1ce4e5b9u64;
+        z = (z ^ (z >> 27)) * 0x94d049bb133111ebu64;
+        z ^ (z >> 31)
+    }
+
+    let mut state = seed;
+    let k1 = next(&mut state);
+    let k2 = next(&mut state);
+    (k1, k2)
+}
+
+/// A key hashed ahead of time.
+///
+/// Tables declared with the `#![seed = N]` option all hash keys in the same
+/// way. A `PhfKey` created with the same seed carries the hash of its key, so
+/// `find_prehashed` can look it up in each of them without hashing it again:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::{PhfMap, PhfSet, PhfKey};
+///
+/// static PRECEDENCE: PhfMap<uint> = phf_map! {
+///     #![seed = 1234]
+///     "+" => 1,
+///     "*" => 2,
+/// };
+///
+/// static COMMUTATIVE: PhfSet = phf_set! {
+///     #![seed = 1234]
+///     "+",
+///     "*",
+/// };
+///
+/// # fn main() {
+/// let key = PhfKey::new("*", 1234);
+/// assert_eq!(Some(&2), PRECEDENCE.find_prehashed(&key));
+/// assert!(COMMUTATIVE.contains_prehashed(&key));
+/// # }
+/// ```
+///
+/// A key can be looked up in tables with another seed as well, but is then
+/// hashed again.
+#[deriving(Clone)]
+pub struct PhfKey<'a> {
+    key: &'a str,
+    k1: u64,
+    k2: u64,
+    hashes: (uint, uint, uint),
+}
+
+impl<'a> PhfKey<'a> {
+    /// Hashes `key` for tables declared with the given seed.
+    pub fn new(key: &'a str, seed: u64) -> PhfKey<'a> {
+        let (k1, k2) = seed_keys(seed);
+        PhfKey {
+            key: key,
+            k1: k1,
+            k2: k2,
+            hashes: hash(key, k1, k2),
+        }
+    }
+
+    /// Returns the key.
+    #[inline]
+    pub fn as_slice(&self) -> &'a str {
+        self.key
+    }
+
+    // Returns the hashes of the key for a table with the given SipHash keys.
+    #[inline]
+    fn hashes(&self, k1: u64, k2: u64) -> (uint, uint, uint) {
+        if self.k1 == k1 && self.k2 == k2 {
+            self.hashes
+        } else {
+            hash(self.key, k1, k2)
+        }
+    }
+}
+
+/// An inconsistency in a map's tables, as reported by `validate`.
+#[deriving(PartialEq, Clone)]
+pub enum ValidationError {
+    /// The map has entries but an empty displacement table.
+    NoDisplacements,
+    /// The map has a displacement table but no entries.
+    NoEntries,
+    /// The index table does not have exactly one slot per entry.
+    ///
+    /// The fields are the length of the index table and the number of entries.
+    IndexCountMismatch(uint, uint),
+    /// A slot of the index table refers to an entry that does not exist.
+    ///
+    /// The fields are the slot and the entry index stored in it.
+    IndexOutOfBounds(uint, uint),
+    /// An entry refers to a value that does not exist.
+    ///
+    /// The fields are the entry's key and the value index stored in it.
+    ValueOutOfBounds(&'static str, uint),
+    /// A key does not hash to its own entry.
+    ///
+    /// The fields are the key, the index of its entry and the index of the
+    /// entry that a lookup of the key would inspect instead.
+    MisplacedKey(&'static str, uint, uint),
+    /// The value of a key does not hash to its own slot of the reverse index
+    /// table of a `PhfBiMap`.
+    ///
+    /// The fields are the key, the index of its entry and the entry that a
+    /// lookup of its value would inspect instead.
+    MisplacedValue(&'static str, uint, uint),
+    /// The entries of a `PhfSortedMap` are not sorted by key.
+    ///
+    /// The fields are two keys, the first of which is stored before the
+    /// second although it is not less than it.
+    OutOfOrder(&'static str, &'static str),
+    /// The table of key lengths is not in descending order.
+    UnsortedLengths,
+    /// The length of a key is missing from the table of key lengths, which
+    /// is not empty.
+    ///
+    /// The field is the key.
+    MissingLength(&'static str),
+    /// The index of entries by key length does not hold each entry once, in
+    /// buckets of keys of the same length ordered by that length.
+    BadLengthIndex,
+}
+
+impl fmt::Show for ValidationError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            NoDisplacements =>
+                write!(fmt, "the displacement table is empty"),
+            NoEntries =>
+                write!(fmt, "the map has displacements but no entries"),
+            IndexCountMismatch(idxs, entries) =>
+                write!(fmt, "the index table has {} slots but there are {} \
+                             entries", idxs, entries),
+            IndexOutOfBounds(slot, idx) =>
+                write!(fmt, "slot {} of the index table refers to entry {}, \
+                             which does not exist", slot, idx),
+            ValueOutOfBounds(key, idx) =>
+                write!(fmt, "the entry for key `{}` refers to value {}, which \
+                             does not exist", key, idx),
+            MisplacedKey(key, idx, found) =>
+                write!(fmt, "key `{}` is stored in entry {} but hashes to \
+                             entry {}", key, idx, found),
+            MisplacedValue(key, idx, found) =>
+                write!(fmt, "the value of key `{}` in entry {} hashes to \
+                             entry {}", key, idx, found),
+            OutOfOrder(first, second) =>
+                write!(fmt, "key `{}` is stored before key `{}`", first,
+                       second),
+            UnsortedLengths =>
+                write!(fmt, "the key length table is not in descending order"),
+            MissingLength(key) =>
+                write!(fmt, "the length of key `{}` is missing from the key \
+                             length table", key),
+            BadLengthIndex =>
+                write!(fmt, "the index of entries by key length is not \
+                             sorted, has a key in the wrong bucket or has the \
+                             wrong number of entries"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Container for PhfMap<T> {
+    #[inline]
+    fn len(&self) -> uint {
+        self.len()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, T> Map<&'a str, T> for PhfMap<T> {
+    #[inline]
+    fn find<'a>(&'a self, key: & &str) -> Option<&'a T> {
+        self.find(key)
+    }
+}
+
+impl<T: fmt::Show> fmt::Show for PhfMap<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(fmt, r"\{"));
+        let mut first = true;
+        for (k, v) in self.entries() {
+            if !first {
+                try!(write!(fmt, ", "));
+            }
+            try!(write!(fmt, "{}: {}", k, v))
+            first = false;
+        }
+        write!(fmt, r"\}")
+    }
+}
+
+// Looks up the prefixes of `s` with `find`, longest first, and returns the
+// first result. Only prefixes with one of the lengths in `lens` are tried. The
+// table of lengths is empty for maps declared without the `longest_prefix`
+// option, in which case every prefix is tried.
+fn find_longest_prefix_by<R>(s: &str, lens: &[uint],
+                             find: |&str| -> Option<R>) -> Option<R> {
+    if lens.is_empty() {
+        for len in range(0, s.len() + 1).rev() {
+            if !s.is_char_boundary(len) {
+                continue;
+            }
+            match find(s.slice_to(len)) {
+                Some(result) => return Some(result),
+                None => {}
+            }
+        }
+        return None;
+    }
+
+    for &len in lens.iter() {
+        if len > s.len() || !s.is_char_boundary(len) {
+            continue;
+        }
+        match find(s.slice_to(len)) {
+            Some(result) => return Some(result),
+            None => {}
+        }
+    }
+    None
+}
+
+// Checks the tables of key lengths used by `find_longest_prefix` and
+// `suggest`.
+fn validate_lens(entries: &[(&'static str, uint)], lens: &[uint],
+                 len_idxs: &[uint], len_buckets: &[(uint, uint)])
+                 -> Result<(), ValidationError> {
+    for pair in lens.windows(2) {
+        if pair[0] <= pair[1] {
+            return Err(UnsortedLengths);
+        }
+    }
+
+    if !lens.is_empty() {
+        for &(key, _) in entries.iter() {
+            if !lens.contains(&key.len()) {
+                return Err(MissingLength(key));
+            }
+        }
+    }
+
+    if len_idxs.len() != entries.len() {
+        return Err(BadLengthIndex);
+    }
+
+    let mut start = 0;
+    let mut last = None;
+    for &(len, end) in len_buckets.iter() {
+        if end <= start || end > len_idxs.len() || last >= Some(len) {
+            return Err(BadLengthIndex);
+        }
+
+        for &idx in len_idxs.slice(start, end).iter() {
+            if idx >= entries.len() {
+                return Err(BadLengthIndex);
+            }
+
+            let (key, _) = entries[idx];
+            if key.char_len() != len {
+                return Err(BadLengthIndex);
+            }
+        }
+
+        start = end;
+        last = Some(len);
+    }
+
+    if start != len_idxs.len() {
+        return Err(BadLengthIndex);
+    }
+
+    Ok(())
+}
+
+impl<T> PhfMap<T> {
+    fn find_index(&self, key: & &str) -> Option<uint> {
+        if self.disps.is_empty() || self.entries.is_empty() {
+            return None;
+        }
+
+        self.find_index_hashed(*key, hash(*key, self.k1, self.k2))
+    }
+
+    fn find_index_prehashed(&self, key: &PhfKey) -> Option<uint> {
+        if self.disps.is_empty() || self.entries.is_empty() {
+            return None;
+        }
+
+        self.find_index_hashed(key.key, key.hashes(self.k1, self.k2))
+    }
+
+    fn find_index_hashed(&self, key: &str, hashes: (uint, uint, uint))
+                         -> Option<uint> {
+        let (g, f1, f2) = hashes;
+        let (d1, d2) = self.disps[g % self.disps.len()];
+        let idx = displace(f1, f2, d1, d2) % self.entries.len();
+        let (s, _) = self.entries[idx];
+        if s == key {
+            Some(idx)
+        } else {
+            None
+        }
+    }
+
+    fn find_entry(&self, key: & &str) -> Option<&'static (&'static str, uint)> {
+        self.find_index(key).map(|idx| &self.entries[idx])
+    }
+
+    /// Returns the number of entries in the map.
+    #[inline]
+    pub fn len(&self) -> uint {
+        self.entries.len()
+    }
+
+    /// Returns true if the map contains no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a reference to the value that `key` maps to.
+    pub fn find<'a>(&'a self, key: & &str) -> Option<&'a T> {
+        self.find_entry(key).map(|&(_, idx)| &self.values[idx])
+    }
+
+    /// Returns a reference to the value that a prehashed key maps to.
+    ///
+    /// The key isn't hashed again if the map was declared with the seed it was
+    /// created with. See `PhfKey`.
+    pub fn find_prehashed<'a>(&'a self, key: &PhfKey) -> Option<&'a T> {
+        self.find_index_prehashed(key).map(|idx| {
+            let (_, value) = self.entries[idx];
+            &self.values[value]
+        })
+    }
+
+    /// Looks up each of `keys`, storing the value it maps to at the same
+    /// position in `values`.
+    ///
+    /// The keys are resolved in batches. Every key of a batch is hashed before
+    /// any displacement is read, and every displacement before any entry. The
+    /// displacements and entries are prefetched as soon as their positions are
+    /// known, so that the cache misses of a batch overlap instead of every
+    /// lookup waiting on its own.
+    ///
+    /// Fails if `keys` and `values` have different lengths.
+    pub fn find_many<'a>(&'a self, keys: &[&str],
+                         values: &mut [Option<&'a T>]) {
+        find_many_batched(self.k1, self.k2, self.disps, self.entries.len(),
+                          self.entries, keys, values, |slot| slot,
+                          |idx| &self.values[idx])
+    }
+
+    /// Returns true if the map contains a value for `key`.
+    #[inline]
+    pub fn contains_key(&self, key: & &str) -> bool {
+        self.find_entry(key).is_some()
+    }
+
+    /// Returns a reference to the map's internal static instance of the given
+    /// key.
+    ///
+    /// This can be useful for interning schemes.
+    pub fn find_key(&self, key: & &str) -> Option<&'static str> {
+        self.find_entry(key).map(|&(s, _)| s)
+    }
+
+    /// Returns the index of the entry for `key`.
+    ///
+    /// Every entry has a distinct index less than `len()`, so the index can
+    /// serve as a compact identifier for the key and be turned back into it
+    /// with `key_at`. Indices follow the order in which `entries` returns
+    /// the entries.
+    pub fn index_of(&self, key: & &str) -> Option<uint> {
+        self.find_index(key)
+    }
+
+    /// Returns the index of the entry for a prehashed key.
+    ///
+    /// See `index_of` and `find_prehashed`.
+    pub fn index_of_prehashed(&self, key: &PhfKey) -> Option<uint> {
+        self.find_index_prehashed(key)
+    }
+
+    /// Returns the key of the entry at `idx`.
+    pub fn key_at(&self, idx: uint) -> Option<&'static str> {
+        self.entries.get(idx).map(|&(key, _)| key)
+    }
+
+    /// Returns the entry at `idx`.
+    pub fn entry_at<'a>(&'a self, idx: uint) -> Option<(&'static str, &'a T)> {
+        self.entries.get(idx).map(|&(key, i)| (key, &self.values[i]))
+    }
+
+    /// Returns the entry whose key is the longest prefix of `s`.
+    ///
+    /// For maps declared with the `longest_prefix` option, the distinct
+    /// lengths of the keys are stored and only one lookup is made for each of
+    /// them, longest first. Other maps look up every prefix of `s`, longest
+    /// first, so the option should be given to maps used this way:
+    ///
+    /// ```rust
+    /// # #![feature(phase)]
+    /// extern crate phf;
+    /// #[phase(syntax)]
+    /// extern crate phf_mac;
+    ///
+    /// use phf::PhfMap;
+    ///
+    /// static OPERATORS: PhfMap<uint> = phf_map! {
+    ///     #![longest_prefix]
+    ///     ">" => 0,
+    ///     ">>" => 1,
+    ///     ">>=" => 2,
+    /// };
+    ///
+    /// # fn main() {
+    /// assert_eq!(Some((">>", &1)), OPERATORS.find_longest_prefix(">>1"));
+    /// # }
+    /// ```
+    pub fn find_longest_prefix<'a>(&'a self, s: &str)
+                                   -> Option<(&'static str, &'a T)> {
+        find_longest_prefix_by(s, self.lens, |prefix| {
+            self.find_entry(&prefix).map(|&(key, idx)| {
+                (key, &self.values[idx])
+            })
+        })
+    }
+
+    /// Returns the keys within `max_distance` edits of `key`, closest first.
+    ///
+    /// An edit inserts, deletes or substitutes a character, or swaps two
+    /// adjacent characters. Keys at the same distance are returned in sorted
+    /// order. Only keys whose length is within `max_distance` characters of
+    /// the length of `key` are compared.
+    ///
+    /// ```rust
+    /// # #![feature(phase)]
+    /// extern crate phf;
+    /// #[phase(syntax)]
+    /// extern crate phf_mac;
+    ///
+    /// use phf::PhfMap;
+    ///
+    /// static COMMANDS: PhfMap<uint> = phf_map! {
+    ///     "build" => 0,
+    ///     "bench" => 1,
+    ///     "check" => 2,
+    /// };
+    ///
+    /// # fn main() {
+    /// assert_eq!(vec!["build"], COMMANDS.suggest("biuld", 1));
+    /// assert_eq!(vec!["bench", "check"], COMMANDS.suggest("beck", 2));
+    /// # }
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn suggest(&self, key: &str, max_distance: uint) -> Vec<&'static str> {
+        suggest::suggest(self.entries, self.len_idxs, self.len_buckets, key,
+                         max_distance)
+    }
+
+    /// Checks that every key in the map can be found by a lookup.
+    ///
+    /// Maps created by the `phf_map` macro always pass this check, but maps
+    /// written out by hand or by other tools may not.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.disps.is_empty() && !self.entries.is_empty() {
+            return Err(NoDisplacements);
+        }
+        if !self.disps.is_empty() && self.entries.is_empty() {
+            return Err(NoEntries);
+        }
+
+        for (i, &(key, idx)) in self.entries.iter().enumerate() {
+            if idx >= self.values.len() {
+                return Err(ValueOutOfBounds(key, idx));
+            }
+
+            let (g, f1, f2) = hash(key, self.k1, self.k2);
+            let (d1, d2) = self.disps[g % self.disps.len()];
+            let found = displace(f1, f2, d1, d2) % self.entries.len();
+            if found != i {
+                return Err(MisplacedKey(key, i, found));
+            }
+        }
+
+        validate_lens(self.entries, self.lens, self.len_idxs,
+                      self.len_buckets)
+    }
+
+    /// Fails if `validate` reports an error.
+    ///
+    /// The check is skipped when compiled with `--cfg ndebug`.
+    pub fn debug_validate(&self) {
+        if cfg!(not(ndebug)) {
+            match self.validate() {
+                Ok(()) => {}
+                Err(err) => fail!("invalid PhfMap: {}", err),
+            }
+        }
+    }
+
+    /// Returns an iterator over the key/value pairs in the map.
+    ///
+    /// Entries are retuned in an arbitrary but fixed order.
+    pub fn entries<'a>(&'a self) -> PhfMapEntries<'a, T> {
+        PhfMapEntries {
+            iter: self.entries.iter(),
+            values: self.values,
+        }
+    }
+
+    /// Returns an iterator over the keys in the map.
+    ///
+    /// Keys are returned in an arbitrary but fixed order.
+    pub fn keys<'a>(&'a self) -> PhfMapKeys<'a, T> {
+        PhfMapKeys { iter: self.entries() }
+    }
+
+    /// Returns an iterator over the values in the map.
+    ///
+    /// Values are returned in an arbitrary but fixed order. A value shared by
+    /// several keys is returned once for each of them.
+    pub fn values<'a>(&'a self) -> PhfMapValues<'a, T> {
+        PhfMapValues { iter: self.entries() }
+    }
+}
+
+impl<T> PhfMap<&'static PhfMap<T>> {
+    /// Looks up a value in a map of maps.
+    ///
+    /// `outer` is looked up in this map, and `inner` in the map that it maps
+    /// to.
+    pub fn find_path<'a>(&'a self, outer: & &str, inner: & &str)
+                         -> Option<&'a T> {
+        match self.find(outer) {
+            Some(map) => map.find(inner),
+            None => None,
+        }
+    }
+}
+
+impl PhfMap<&'static PhfSet> {
+    /// Checks for a value in a map of sets.
+    ///
+    /// `outer` is looked up in this map, and `inner` in the set that it maps
+    /// to.
+    pub fn contains_path(&self, outer: & &str, inner: & &str) -> bool {
+        match self.find(outer) {
+            Some(set) => set.contains(inner),
+            None => false,
+        }
+    }
+}
+
+/// An iterator over the key/value pairs in a `PhfMap`.
+pub struct PhfMapEntries<'a, T> {
+    iter: slice::Items<'a, (&'static str, uint)>,
+    values: &'a [T],
+}
+
+impl<'a, T> Iterator<(&'static str, &'a T)> for PhfMapEntries<'a, T> {
+    fn next(&mut self) -> Option<(&'static str, &'a T)> {
+        let values = self.values;
+        self.iter.next().map(|&(key, idx)| (key, &values[idx]))
+    }
+
+    fn size_hint(&self) -> (uint, Option<uint>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An iterator over the keys in a `PhfMap`.
+pub struct PhfMapKeys<'a, T> {
+    iter: PhfMapEntries<'a, T>,
+}
+
+impl<'a, T> Iterator<&'static str> for PhfMapKeys<'a, T> {
+    fn next(&mut self) -> Option<&'static str> {
+        self.iter.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (uint, Option<uint>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An iterator over the values in a `PhfMap`.
+pub struct PhfMapValues<'a, T> {
+    iter: PhfMapEntries<'a, T>,
+}
+
+impl<'a, T> Iterator<&'a T> for PhfMapValues<'a, T> {
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (uint, Option<uint>) {
+        self.iter.size_hint()
+    }
+}
+
+// The sets and the maps built on top of another map forward most of their
+// methods to it. Each macro generates a single inherent impl, so a type may
+// still define the methods which differ, such as `index_of`, alongside it.
+
+macro_rules! forward_set_methods(
+    ($name:ident) => (
+        impl $name {
+            /// Returns the number of values in the set.
+            #[inline]
+            pub fn len(&self) -> uint {
+                self.map.len()
+            }
+
+            /// Returns true if the set contains no values.
+            #[inline]
+            pub fn is_empty(&self) -> bool {
+                self.len() == 0
+            }
+
+            /// Returns true if the set contains the given value.
+            #[inline]
+            pub fn contains(&self, value: & &str) -> bool {
+                self.map.contains_key(value)
+            }
+
+            /// Returns true if the set contains a prehashed value.
+            ///
+            /// See `PhfMap::find_prehashed`.
+            #[inline]
+            pub fn contains_prehashed(&self, value: &PhfKey) -> bool {
+                self.map.find_prehashed(value).is_some()
+            }
+
+            /// Returns the index of a prehashed value in the set.
+            ///
+            /// See `PhfMap::find_prehashed`.
+            #[inline]
+            pub fn index_of_prehashed(&self, value: &PhfKey) -> Option<uint> {
+                self.map.index_of_prehashed(value)
+            }
+
+            /// Returns a reference to the set's internal static instance of
+            /// the given key.
+            ///
+            /// This can be useful for interning schemes.
+            #[inline]
+            pub fn find_key(&self, key: & &str) -> Option<&'static str> {
+                self.map.find_key(key)
+            }
+
+            /// Returns the key at `idx`.
+            #[inline]
+            pub fn key_at(&self, idx: uint) -> Option<&'static str> {
+                self.map.key_at(idx)
+            }
+
+            /// Checks that every value in the set can be found by a lookup,
+            /// along with any checks made by the map the set is built on.
+            #[inline]
+            pub fn validate(&self) -> Result<(), ValidationError> {
+                self.map.validate()
+            }
+
+            /// Fails if `validate` reports an error.
+            ///
+            /// The check is skipped when compiled with `--cfg ndebug`.
+            #[inline]
+            pub fn debug_validate(&self) {
+                self.map.debug_validate()
+            }
+        }
+    )
+)
+
+macro_rules! forward_map_methods(
+    ($name:ident, $entries:ident, $keys:ident, $values:ident) => (
+        impl<T> $name<T> {
+            /// Returns the number of entries in the map.
+            #[inline]
+            pub fn len(&self) -> uint {
+                self.map.len()
+            }
+
+            /// Returns true if the map contains no entries.
+            #[inline]
+            pub fn is_empty(&self) -> bool {
+                self.map.is_empty()
+            }
+
+            /// Returns a reference to the value that `key` maps to.
+            #[inline]
+            pub fn find<'a>(&'a self, key: & &str) -> Option<&'a T> {
+                self.map.find(key)
+            }
+
+            /// Returns a reference to the value that a prehashed key maps to.
+            ///
+            /// See `PhfMap::find_prehashed`.
+            #[inline]
+            pub fn find_prehashed<'a>(&'a self, key: &PhfKey)
+                                      -> Option<&'a T> {
+                self.map.find_prehashed(key)
+            }
+
+            /// Returns the index of the entry for a prehashed key.
+            ///
+            /// See `PhfMap::find_prehashed`.
+            #[inline]
+            pub fn index_of_prehashed(&self, key: &PhfKey) -> Option<uint> {
+                self.map.index_of_prehashed(key)
+            }
+
+            /// Looks up each of `keys`, storing the value it maps to at the
+            /// same position in `values`.
+            ///
+            /// See `PhfMap::find_many`.
+            #[inline]
+            pub fn find_many<'a>(&'a self, keys: &[&str],
+                                 values: &mut [Option<&'a T>]) {
+                self.map.find_many(keys, values)
+            }
+
+            /// Returns true if the map contains a value for `key`.
+            #[inline]
+            pub fn contains_key(&self, key: & &str) -> bool {
+                self.map.contains_key(key)
+            }
+
+            /// Returns a reference to the map's internal static instance of
+            /// the given key.
+            ///
+            /// This can be useful for interning schemes.
+            #[inline]
+            pub fn find_key(&self, key: & &str) -> Option<&'static str> {
+                self.map.find_key(key)
+            }
+
+            /// Returns the key of the entry at `idx`.
+            #[inline]
+            pub fn key_at(&self, idx: uint) -> Option<&'static str> {
+                self.map.key_at(idx)
+            }
+
+            /// Returns the entry at `idx`.
+            #[inline]
+            pub fn entry_at<'a>(&'a self, idx: uint)
+                                -> Option<(&'static str, &'a T)> {
+                self.map.entry_at(idx)
+            }
+
+            /// Returns an iterator over the key/value pairs in the map.
+            ///
+            /// Entries are returned in the order given by `index_of`.
+            #[inline]
+            pub fn entries<'a>(&'a self) -> $entries<'a, T> {
+                self.map.entries()
+            }
+
+            /// Returns an iterator over the keys in the map.
+            ///
+            /// Keys are returned in the order given by `index_of`.
+            #[inline]
+            pub fn keys<'a>(&'a self) -> $keys<'a, T> {
+                self.map.keys()
+            }
+
+            /// Returns an iterator over the values in the map.
+            ///
+            /// Values are returned in the order given by `index_of`.
+            #[inline]
+            pub fn values<'a>(&'a self) -> $values<'a, T> {
+                self.map.values()
+            }
+        }
+    )
+)
+
+// `find_longest_prefix` and `suggest`, for the kinds which support them.
+macro_rules! forward_prefix_methods(
+    (set $name:ident) => (
+        impl $name {
+            /// Returns the value of the set which is the longest prefix of
+            /// `s`.
+            ///
+            /// See `PhfMap::find_longest_prefix`.
+            #[inline]
+            pub fn find_longest_prefix(&self, s: &str) -> Option<&'static str> {
+                self.map.find_longest_prefix(s).map(|(key, _)| key)
+            }
+
+            /// Returns the values of the set within `max_distance` edits of
+            /// `key`, closest first.
+            ///
+            /// See `PhfMap::suggest`.
+            #[cfg(feature = "std")]
+            #[inline]
+            pub fn suggest(&self, key: &str, max_distance: uint)
+                           -> Vec<&'static str> {
+                self.map.suggest(key, max_distance)
+            }
+        }
+    );
+    (map $name:ident) => (
+        impl<T> $name<T> {
+            /// Returns the entry whose key is the longest prefix of `s`.
+            ///
+            /// See `PhfMap::find_longest_prefix`.
+            #[inline]
+            pub fn find_longest_prefix<'a>(&'a self, s: &str)
+                                           -> Option<(&'static str, &'a T)> {
+                self.map.find_longest_prefix(s)
+            }
+
+            /// Returns the keys within `max_distance` edits of `key`, closest
+            /// first.
+            ///
+            /// See `PhfMap::suggest`.
+            #[cfg(feature = "std")]
+            #[inline]
+            pub fn suggest(&self, key: &str, max_distance: uint)
+                           -> Vec<&'static str> {
+                self.map.suggest(key, max_distance)
+            }
+        }
+    )
+)
+
+/// An immutable set constructed at compile time.
+///
+/// `PhfSet`s may be created with the `phf_set` macro:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::PhfSet;
+///
+/// static MY_SET: PhfSet = phf_set! {
+///    "hello",
+///    "world",
+/// };
+///
+/// # fn main() {}
+/// ```
+///
+/// # Note
+///
+/// The fields of this struct are public so that they may be initialized by the
+/// `phf_set` macro and by code generated with the `phf_codegen` crate. They are
+/// subject to change at any time and should never be accessed directly.
+pub struct PhfSet {
+    #[doc(hidden)]
+    pub map: PhfMap<()>
+}
+
+impl fmt::Show for PhfSet {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(fmt, r"\{"));
+        let mut first = true;
+        for entry in self.iter() {
+            if !first {
+                try!(write!(fmt, ", "));
+            }
+            try!(write!(fmt, "{}", entry));
+            first = false;
+        }
+        write!(fmt, r"\}")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Container for PhfSet {
+    #[inline]
+    fn len(&self) -> uint {
+        self.len()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Set<&'a str> for PhfSet {
+    #[inline]
+    fn contains(&self, value: & &'a str) -> bool {
+        self.contains(value)
+    }
+
+    #[inline]
+    fn is_disjoint(&self, other: &PhfSet) -> bool {
+        self.is_disjoint(other)
+    }
+
+    #[inline]
+    fn is_subset(&self, other: &PhfSet) -> bool {
+        self.is_subset(other)
+    }
+}
+
+impl PhfSet {
+    /// Returns true if the set has no values in common with `other`.
+    #[inline]
+    pub fn is_disjoint(&self, other: &PhfSet) -> bool {
+        !self.iter().any(|value| other.contains(&value))
+    }
+
+    /// Returns true if every value in the set is also in `other`.
+    #[inline]
+    pub fn is_subset(&self, other: &PhfSet) -> bool {
+        self.iter().all(|value| other.contains(&value))
+    }
+
+    /// Returns true if every value in `other` is also in the set.
+    #[inline]
+    pub fn is_superset(&self, other: &PhfSet) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns the index of `value` in the set.
+    ///
+    /// See `PhfMap::index_of`.
+    #[inline]
+    pub fn index_of(&self, value: & &str) -> Option<uint> {
+        self.map.index_of(value)
+    }
+
+    /// Returns an iterator over the values in the set.
+    ///
+    /// Values are returned in an arbitrary but fixed order.
+    #[inline]
+    pub fn iter<'a>(&'a self) -> PhfSetValues<'a> {
+        PhfSetValues { iter: self.map.keys() }
+    }
+}
+
+forward_set_methods!(PhfSet)
+forward_prefix_methods!(set PhfSet)
+
+/// An iterator over the values in a `PhfSet`.
+pub struct PhfSetValues<'a> {
+    iter: PhfMapKeys<'a, ()>,
+}
+
+impl<'a> Iterator<&'static str> for PhfSetValues<'a> {
+    #[inline]
+    fn next(&mut self) -> Option<&'static str> {
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (uint, Option<uint>) {
+        self.iter.size_hint()
+    }
+}
+
+/// A map with a fixed set of keys whose values may change at run time.
+///
+/// The keys are the values of a static `PhfSet`. The value of each key is
+/// stored at the key's index in the set, so lookups make a single probe and
+/// the values are never moved.
+///
+/// A `PhfTable` allocates its values at run time, so it can't be stored in a
+/// static. Values shared between threads through a static, such as counters,
+/// can be kept in a static array indexed by `PhfSet::index_of` instead, for
+/// example a `[AtomicUint, ..N]` of `INIT_ATOMIC_UINT`s. Generating such
+/// arrays is outside the scope of this type.
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::{PhfSet, PhfTable};
+///
+/// static KEYWORDS: PhfSet = phf_set! {
+///     "loop",
+///     "while",
+/// };
+///
+/// # fn main() {
+/// let mut counts = PhfTable::with_value(&KEYWORDS, 0u);
+/// *counts.find_mut(&"loop").unwrap() += 1;
+/// assert_eq!(Some(&1), counts.find(&"loop"));
+/// assert_eq!(Ok(0), counts.insert_existing(&"while", 5));
+/// assert_eq!(Err(3), counts.insert_existing(&"for", 3));
+/// # }
+/// ```
+#[cfg(feature = "std")]
+pub struct PhfTable<V> {
+    keys: &'static PhfSet,
+    values: Vec<V>,
+}
+
+#[cfg(feature = "std")]
+impl<V: fmt::Show> fmt::Show for PhfTable<V> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(fmt, r"\{"));
+        let mut first = true;
+        for (k, v) in self.entries() {
+            if !first {
+                try!(write!(fmt, ", "));
+            }
+            try!(write!(fmt, "{}: {}", k, v))
+            first = false;
+        }
+        write!(fmt, r"\}")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V> Container for PhfTable<V> {
+    #[inline]
+    fn len(&self) -> uint {
+        self.len()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, V> Map<&'a str, V> for PhfTable<V> {
+    #[inline]
+    fn find<'a>(&'a self, key: & &str) -> Option<&'a V> {
+        self.find(key)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V> PhfTable<V> {
+    /// Creates a table over the values of `keys`, initializing the value of
+    /// each key with `init`.
+    pub fn new(keys: &'static PhfSet, init: |&'static str| -> V)
+               -> PhfTable<V> {
+        PhfTable {
+            keys: keys,
+            values: keys.iter().map(|key| init(key)).collect(),
+        }
+    }
+
+    /// Returns the set of the table's keys.
+    #[inline]
+    pub fn keys(&self) -> &'static PhfSet {
+        self.keys
+    }
+
+    /// Returns the number of entries in the table.
+    #[inline]
+    pub fn len(&self) -> uint {
+        self.values.len()
+    }
+
+    /// Returns true if the table contains no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if `key` is one of the table's keys.
+    #[inline]
+    pub fn contains_key(&self, key: & &str) -> bool {
+        self.keys.contains(key)
+    }
+
+    /// Returns a reference to the value of `key`.
+    pub fn find<'a>(&'a self, key: & &str) -> Option<&'a V> {
+        self.keys.index_of(key).map(|idx| self.values.get(idx))
+    }
+
+    /// Returns a mutable reference to the value of `key`.
+    pub fn find_mut<'a>(&'a mut self, key: & &str) -> Option<&'a mut V> {
+        match self.keys.index_of(key) {
+            Some(idx) => Some(self.values.get_mut(idx)),
+            None => None,
+        }
+    }
+
+    /// Replaces the value of `key`, returning the previous value.
+    ///
+    /// The keys of a table are fixed, so if `key` is not one of them `value`
+    /// is returned as an error instead.
+    pub fn insert_existing(&mut self, key: & &str, value: V) -> Result<V, V> {
+        match self.find_mut(key) {
+            Some(slot) => Ok(mem::replace(slot, value)),
+            None => Err(value),
+        }
+    }
+
+    /// Returns an iterator over the key/value pairs in the table.
+    ///
+    /// Entries are returned in the order of the values of the key set.
+    pub fn entries<'a>(&'a self) -> PhfTableEntries<'a, V> {
+        PhfTableEntries {
+            iter: self.keys.map.entries.iter().zip(self.values.iter()),
+        }
+    }
+
+    /// Returns an iterator over the key/value pairs in the table, with mutable
+    /// references to the values.
+    ///
+    /// Entries are returned in the order of the values of the key set.
+    pub fn mut_entries<'a>(&'a mut self) -> PhfTableMutEntries<'a, V> {
+        PhfTableMutEntries {
+            iter: self.keys.map.entries.iter().zip(self.values.mut_iter()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<V: Clone> PhfTable<V> {
+    /// Creates a table over the values of `keys`, giving each key a copy of
+    /// `value`.
+    pub fn with_value(keys: &'static PhfSet, value: V) -> PhfTable<V> {
+        PhfTable {
+            keys: keys,
+            values: Vec::from_elem(keys.len(), value),
+        }
+    }
+}
+
+/// An iterator over the entries in a `PhfTable`.
+#[cfg(feature = "std")]
+pub struct PhfTableEntries<'a, V> {
+    iter: iter::Zip<slice::Items<'static, (&'static str, uint)>,
+                    slice::Items<'a, V>>,
+}
+
+#[cfg(feature = "std")]
+impl<'a, V> Iterator<(&'static str, &'a V)> for PhfTableEntries<'a, V> {
+    #[inline]
+    fn next(&mut self) -> Option<(&'static str, &'a V)> {
+        self.iter.next().map(|(&(key, _), value)| (key, value))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (uint, Option<uint>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An iterator over the entries in a `PhfTable` with mutable references to
+/// the values.
+#[cfg(feature = "std")]
+pub struct PhfTableMutEntries<'a, V> {
+    iter: iter::Zip<slice::Items<'static, (&'static str, uint)>,
+                    slice::MutItems<'a, V>>,
+}
+
+#[cfg(feature = "std")]
+impl<'a, V> Iterator<(&'static str, &'a mut V)> for PhfTableMutEntries<'a, V> {
+    #[inline]
+    fn next(&mut self) -> Option<(&'static str, &'a mut V)> {
+        self.iter.next().map(|(&(key, _), value)| (key, value))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (uint, Option<uint>) {
+        self.iter.size_hint()
+    }
+}
+
+/// An order-preserving immutable map constructed at compile time.
+///
+/// Unlike a `PhfMap`, the order of entries in a `PhfOrderedMap` is guaranteed
+/// to be the order the entries were listed in.
+///
+/// `PhfOrderedMap`s may be created with the `phf_ordered_map` macro:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::PhfOrderedMap;
+///
+/// static MY_MAP: PhfOrderedMap<int> = phf_ordered_map! {
+///    "hello" => 10,
+///    "world" => 11,
+/// };
+///
+/// # fn main() {}
+/// ```
+///
+/// # Note
+///
+/// The fields of this struct are public so that they may be initialized by the
+/// `phf_ordered_map` macro and by code generated with the `phf_codegen` crate.
+/// They are subject to change at any time and should never be accessed
+/// directly.
+pub struct PhfOrderedMap<T> {
+    #[doc(hidden)]
+    pub k1: u64,
+    #[doc(hidden)]
+    pub k2: u64,
+    #[doc(hidden)]
+    pub disps: &'static [(uint, uint)],
+    #[doc(hidden)]
+    pub idxs: &'static [uint],
+    #[doc(hidden)]
+    pub entries: &'static [(&'static str, uint)],
+    #[doc(hidden)]
+    pub values: &'static [T],
+    #[doc(hidden)]
+    pub lens: &'static [uint],
+    #[doc(hidden)]
+    pub len_idxs: &'static [uint],
+    #[doc(hidden)]
+    pub len_buckets: &'static [(uint, uint)],
+}
+
+impl<T: fmt::Show> fmt::Show for PhfOrderedMap<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(fmt, r"\{"));
+        let mut first = true;
+        for (k, v) in self.entries() {
+            if !first {
+                try!(write!(fmt, ", "));
+            }
+            try!(write!(fmt, "{}: {}", k, v))
+            first = false;
+        }
+        write!(fmt, r"\}")
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Container for PhfOrderedMap<T> {
+    #[inline]
+    fn len(&self) -> uint {
+        self.len()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, T> Map<&'a str, T> for PhfOrderedMap<T> {
+    #[inline]
+    fn find<'a>(&'a self, key: & &str) -> Option<&'a T> {
+        self.find(key)
+    }
+}
+
+impl<T> PhfOrderedMap<T> {
+    fn find_index(&self, key: & &str) -> Option<uint> {
+        if self.disps.is_empty() || self.idxs.is_empty() {
+            return None;
+        }
+
+        self.find_index_hashed(*key, hash(*key, self.k1, self.k2))
+    }
+
+    fn find_index_prehashed(&self, key: &PhfKey) -> Option<uint> {
+        if self.disps.is_empty() || self.idxs.is_empty() {
+            return None;
+        }
+
+        self.find_index_hashed(key.key, key.hashes(self.k1, self.k2))
+    }
+
+    fn find_index_hashed(&self, key: &str, hashes: (uint, uint, uint))
+                         -> Option<uint> {
+        let (g, f1, f2) = hashes;
+        let (d1, d2) = self.disps[g % self.disps.len()];
+        let idx = self.idxs[displace(f1, f2, d1, d2) % self.idxs.len()];
+        let (s, _) = self.entries[idx];
+
+        if s == key {
+            Some(idx)
+        } else {
+            None
+        }
+    }
+
+    fn find_entry(&self, key: & &str) -> Option<&'static (&'static str, uint)> {
+        self.find_index(key).map(|idx| &self.entries[idx])
+    }
+
+    /// Returns the number of entries in the map.
+    #[inline]
+    pub fn len(&self) -> uint {
+        self.entries.len()
+    }
+
+    /// Returns true if the map contains no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns a reference to the value that `key` maps to.
+    pub fn find<'a>(&'a self, key: & &str) -> Option<&'a T> {
+        self.find_entry(key).map(|&(_, idx)| &self.values[idx])
+    }
+
+    /// Returns a reference to the value that a prehashed key maps to.
+    ///
+    /// The key isn't hashed again if the map was declared with the seed it was
+    /// created with. See `PhfKey`.
+    pub fn find_prehashed<'a>(&'a self, key: &PhfKey) -> Option<&'a T> {
+        self.find_index_prehashed(key).map(|idx| {
+            let (_, value) = self.entries[idx];
+            &self.values[value]
+        })
+    }
+
+    /// Looks up each of `keys`, storing the value it maps to at the same
+    /// position in `values`.
+    ///
+    /// See `PhfMap::find_many`.
+    pub fn find_many<'a>(&'a self, keys: &[&str],
+                         values: &mut [Option<&'a T>]) {
+        find_many_batched(self.k1, self.k2, self.disps, self.idxs.len(),
+                          self.entries, keys, values, |slot| self.idxs[slot],
+                          |idx| &self.values[idx])
+    }
+
+    /// Returns true if the map contains a value for `key`.
+    #[inline]
+    pub fn contains_key(&self, key: & &str) -> bool {
+        self.find_entry(key).is_some()
+    }
+
+    /// Returns a reference to the map's internal static instance of the given
+    /// key.
+    ///
+    /// This can be useful for interning schemes.
+    pub fn find_key(&self, key: & &str) -> Option<&'static str> {
+        self.find_entry(key).map(|&(s, _)| s)
+    }
+
+    /// Returns the index of the entry for `key`.
+    ///
+    /// Every entry has a distinct index less than `len()`, so the index can
+    /// serve as a compact identifier for the key and be turned back into it
+    /// with `key_at`. Indices follow the order in which the entries were
+    /// defined.
+    pub fn index_of(&self, key: & &str) -> Option<uint> {
+        self.find_index(key)
+    }
+
+    /// Returns the index of the entry for a prehashed key.
+    ///
+    /// See `index_of` and `find_prehashed`.
+    pub fn index_of_prehashed(&self, key: &PhfKey) -> Option<uint> {
+        self.find_index_prehashed(key)
+    }
+
+    /// Returns the key of the entry at `idx`.
+    pub fn key_at(&self, idx: uint) -> Option<&'static str> {
+        self.entries.get(idx).map(|&(key, _)| key)
+    }
+
+    /// Returns the entry at `idx`.
+    pub fn entry_at<'a>(&'a self, idx: uint) -> Option<(&'static str, &'a T)> {
+        self.entries.get(idx).map(|&(key, i)| (key, &self.values[i]))
+    }
+
+    /// Returns the entry whose key is the longest prefix of `s`.
+    ///
+    /// See `PhfMap::find_longest_prefix`.
+    pub fn find_longest_prefix<'a>(&'a self, s: &str)
+                                   -> Option<(&'static str, &'a T)> {
+        find_longest_prefix_by(s, self.lens, |prefix| {
+            self.find_entry(&prefix).map(|&(key, idx)| {
+                (key, &self.values[idx])
+            })
+        })
+    }
+
+    /// Returns the keys within `max_distance` edits of `key`, closest first.
+    ///
+    /// See `PhfMap::suggest`.
+    #[cfg(feature = "std")]
+    pub fn suggest(&self, key: &str, max_distance: uint) -> Vec<&'static str> {
+        suggest::suggest(self.entries, self.len_idxs, self.len_buckets, key,
+                         max_distance)
+    }
+
+    /// Checks that every key in the map can be found by a lookup.
+    ///
+    /// Maps created by the `phf_ordered_map` macro always pass this check, but
+    /// maps written out by hand or by other tools may not.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.disps.is_empty() && !self.entries.is_empty() {
+            return Err(NoDisplacements);
+        }
+        if !self.disps.is_empty() && self.entries.is_empty() {
+            return Err(NoEntries);
+        }
+
+        if self.idxs.len() != self.entries.len() {
+            return Err(IndexCountMismatch(self.idxs.len(), self.entries.len()));
+        }
+
+        for (slot, &idx) in self.idxs.iter().enumerate() {
+            if idx >= self.entries.len() {
+                return Err(IndexOutOfBounds(slot, idx));
+            }
+        }
+
+        for (i, &(key, idx)) in self.entries.iter().enumerate() {
+            if idx >= self.values.len() {
+                return Err(ValueOutOfBounds(key, idx));
+            }
+
+            let (g, f1, f2) = hash(key, self.k1, self.k2);
+            let (d1, d2) = self.disps[g % self.disps.len()];
+            let found = self.idxs[displace(f1, f2, d1, d2) % self.idxs.len()];
+            if found != i {
+                return Err(MisplacedKey(key, i, found));
+            }
+        }
+
+        validate_lens(self.entries, self.lens, self.len_idxs,
+                      self.len_buckets)
+    }
+
+    /// Fails if `validate` reports an error.
+    ///
+    /// The check is skipped when compiled with `--cfg ndebug`.
+    pub fn debug_validate(&self) {
+        if cfg!(not(ndebug)) {
+            match self.validate() {
+                Ok(()) => {}
+                Err(err) => fail!("invalid PhfOrderedMap: {}", err),
+            }
+        }
+    }
+
+    /// Returns an iterator over the key/value pairs in the map.
+    ///
+    /// Entries are retuned in the same order in which they were defined.
+    pub fn entries<'a>(&'a self) -> PhfOrderedMapEntries<'a, T> {
+        PhfOrderedMapEntries {
+            iter: self.entries.iter(),
+            values: self.values,
+        }
+    }
+
+    /// Returns an iterator over the keys in the map.
+    ///
+    /// Keys are returned in the same order in which they were defined.
+    pub fn keys<'a>(&'a self) -> PhfOrderedMapKeys<'a, T> {
+        PhfOrderedMapKeys { iter: self.entries() }
+    }
+
+    /// Returns an iterator over the values in the map.
+    ///
+    /// Values are returned in the same order in which their keys were
+    /// defined. A value shared by several keys is returned once for each of
+    /// them.
+    pub fn values<'a>(&'a self) -> PhfOrderedMapValues<'a, T> {
+        PhfOrderedMapValues { iter: self.entries() }
+    }
+}
+
+/// An iterator over the entries in a `PhfOrderedMap`.
+pub struct PhfOrderedMapEntries<'a, T> {
+    iter: slice::Items<'a, (&'static str, uint)>,
+    values: &'a [T],
+}
+
+impl<'a, T> Iterator<(&'static str, &'a T)> for PhfOrderedMapEntries<'a, T> {
+    fn next(&mut self) -> Option<(&'static str, &'a T)> {
+        let values = self.values;
+        self.iter.next().map(|&(key, idx)| (key, &values[idx]))
+    }
+
+    fn size_hint(&self) -> (uint, Option<uint>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator<(&'static str, &'a T)>
+        for PhfOrderedMapEntries<'a, T> {
+    fn next_back(&mut self) -> Option<(&'static str, &'a T)> {
+        let values = self.values;
+        self.iter.next_back().map(|&(key, idx)| (key, &values[idx]))
+    }
+}
+
+impl<'a, T> RandomAccessIterator<(&'static str, &'a T)>
+        for PhfOrderedMapEntries<'a, T> {
+    fn indexable(&self) -> uint {
+        self.iter.indexable()
+    }
+
+    fn idx(&mut self, index: uint) -> Option<(&'static str, &'a T)> {
+        let values = self.values;
+        self.iter.idx(index).map(|&(key, idx)| (key, &values[idx]))
+    }
+}
+
+impl<'a, T> ExactSize<(&'static str, &'a T)> for PhfOrderedMapEntries<'a, T> {}
+
+/// An iterator over the keys in a `PhfOrderedMap`.
+pub struct PhfOrderedMapKeys<'a, T> {
+    iter: PhfOrderedMapEntries<'a, T>,
+}
+
+impl<'a, T> Iterator<&'static str> for PhfOrderedMapKeys<'a, T> {
+    fn next(&mut self) -> Option<&'static str> {
+        self.iter.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (uint, Option<uint>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator<&'static str> for PhfOrderedMapKeys<'a, T> {
+    fn next_back(&mut self) -> Option<&'static str> {
+        self.iter.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<'a, T> RandomAccessIterator<&'static str> for PhfOrderedMapKeys<'a, T> {
+    fn indexable(&self) -> uint {
+        self.iter.indexable()
+    }
+
+    fn idx(&mut self, index: uint) -> Option<&'static str> {
+        self.iter.idx(index).map(|(key, _)| key)
+    }
+}
+
+impl<'a, T> ExactSize<&'static str> for PhfOrderedMapKeys<'a, T> {}
+
+/// An iterator over the values in a `PhfOrderedMap`.
+pub struct PhfOrderedMapValues<'a, T> {
+    iter: PhfOrderedMapEntries<'a, T>,
+}
+
+impl<'a, T> Iterator<&'a T> for PhfOrderedMapValues<'a, T> {
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (uint, Option<uint>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T> DoubleEndedIterator<&'a T> for PhfOrderedMapValues<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.iter.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<'a, T> RandomAccessIterator<&'a T> for PhfOrderedMapValues<'a, T> {
+    fn indexable(&self) -> uint {
+        self.iter.indexable()
+    }
+
+    fn idx(&mut self, index: uint) -> Option<&'a T> {
+        self.iter.idx(index).map(|(_, value)| value)
+    }
+}
+
+impl<'a, T> ExactSize<&'a T> for PhfOrderedMapValues<'a, T> {}
+
+/// An order-preserving immutable set constructed at compile time.
+///
+/// Unlike a `PhfSet`, the order of entries in a `PhfOrderedSet` is guaranteed
+/// to be the order the entries were listed in.
+///
+/// `PhfOrderedSet`s may be created with the `phf_ordered_set` macro:
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::PhfOrderedSet;
+///
+/// static MY_SET: PhfOrderedSet = phf_ordered_set! {
+///    "hello",
+///    "world",
+/// };
+///
+/// # fn main() {}
+/// ```
+///
+/// # Note
+///
+/// The fields of this struct are public so that they may be initialized by the
+/// `phf_ordered_set` macro and by code generated with the `phf_codegen` crate.
+/// They are subject to change at any time and should never be accessed
+/// directly.
+pub struct PhfOrderedSet {
+    #[doc(hidden)]
+    pub map: PhfOrderedMap<()>,
+}
+
+impl fmt::Show for PhfOrderedSet {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(fmt, r"\{"));
+        let mut first = true;
+        for entry in self.iter() {
+            if !first {
+                try!(write!(fmt, ", "));
+            }
+            try!(write!(fmt, "{}", entry));
+            first = false;
+        }
+        write!(fmt, r"\}")
+    }
+}
+
+#[cfg(feature = "std")]
+impl Container for PhfOrderedSet {
+    #[inline]
+    fn len(&self) -> uint {
+        self.len()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> Set<&'a str> for PhfOrderedSet {
+    #[inline]
+    fn contains(&self, value: & &'a str) -> bool {
+        self.contains(value)
+    }
+
+    #[inline]
+    fn is_disjoint(&self, other: &PhfOrderedSet) -> bool {
+        self.is_disjoint(other)
+    }
+
+    #[inline]
+    fn is_subset(&self, other: &PhfOrderedSet) -> bool {
+        self.is_subset(other)
+    }
+}
+
+impl PhfOrderedSet {
+    /// Returns true if the set has no values in common with `other`.
+    #[inline]
+    pub fn is_disjoint(&self, other: &PhfOrderedSet) -> bool {
+        !self.iter().any(|value| other.contains(&value))
+    }
+
+    /// Returns true if every value in the set is also in `other`.
+    #[inline]
+    pub fn is_subset(&self, other: &PhfOrderedSet) -> bool {
+        self.iter().all(|value| other.contains(&value))
+    }
+
+    /// Returns true if every value in `other` is also in the set.
+    #[inline]
+    pub fn is_superset(&self, other: &PhfOrderedSet) -> bool {
+        other.is_subset(self)
+    }
+
+    /// Returns the index of `value` in the set.
+    ///
+    /// See `PhfOrderedMap::index_of`.
+    #[inline]
+    pub fn index_of(&self, value: & &str) -> Option<uint> {
+        self.map.index_of(value)
+    }
+
+    /// Returns an iterator over the values in the set.
+    ///
+    /// Values are returned in the same order in which they were defined.
+    #[inline]
+    pub fn iter<'a>(&'a self) -> PhfOrderedSetValues<'a> {
+        PhfOrderedSetValues { iter: self.map.keys() }
+    }
+}
+
+forward_set_methods!(PhfOrderedSet)
+forward_prefix_methods!(set PhfOrderedSet)
+
+/// An iterator over the values in a `PhfOrderedSet`.
+pub struct PhfOrderedSetValues<'a> {
+    iter: PhfOrderedMapKeys<'a, ()>,
+}
+
+impl<'a> Iterator<&'static str> for PhfOrderedSetValues<'a> {
+    #[inline]
+    fn next(&mut self) -> Option<&'static str> {
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (uint, Option<uint>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator<&'static str> for PhfOrderedSetValues<'a> {
+    #[inline]
+    fn next_back(&mut self) -> Option<&'static str> {
+        self.iter.next_back()
+    }
+}
+
+impl<'a> RandomAccessIterator<&'static str> for PhfOrderedSetValues<'a> {
+    #[inline]
+    fn indexable(&self) -> uint {
+        self.iter.indexable()
+    }
+
+    #[inline]
+    fn idx(&mut self, index: uint) -> Option<&'static str> {
+        self.iter.idx(index)
+    }
+}
+
+impl<'a> ExactSize<&'static str> for PhfOrderedSetValues<'a> {}
+
+/// An immutable map constructed at compile time which can also be searched by
+/// value.
+///
+/// Each value may be used by only one key. Besides the perfect hash function
+/// over the keys, the map has one over the values which refers to the same
+/// entries, so that `find_by_value` is as fast as `find`.
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::PhfBiMap;
+///
+/// static CODECS: PhfBiMap<u32> = phf_bimap! {
+///    "mp3" => 1,
+///    "ogg" => 2,
+///    "flac" => 3,
+/// };
+///
+/// fn main() {
+///     assert_eq!(Some(&2), CODECS.find(&"ogg"));
+///     assert_eq!(Some("flac"), CODECS.find_by_value(&3));
+/// }
+/// ```
+///
+/// The values given to `phf_bimap!` must be literals: strings, integers,
+/// characters or booleans. Their type must implement `PhfHash`.
+///
+/// # Note
+///
+/// The fields of this struct are public so that they may be initialized by the
+/// `phf_bimap` macro. They are subject to change at any time and should never
+/// be accessed directly.
+pub struct PhfBiMap<T> {
+    #[doc(hidden)]
+    pub map: PhfMap<T>,
+    #[doc(hidden)]
+    pub k1: u64,
+    #[doc(hidden)]
+    pub k2: u64,
+    #[doc(hidden)]
+    pub disps: &'static [(uint, uint)],
+    #[doc(hidden)]
+    pub idxs: &'static [uint],
+}
+
+impl<T: fmt::Show> fmt::Show for PhfBiMap<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.map.fmt(fmt)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T> Container for PhfBiMap<T> {
+    #[inline]
+    fn len(&self) -> uint {
+        self.len()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, T> Map<&'a str, T> for PhfBiMap<T> {
+    #[inline]
+    fn find<'a>(&'a self, key: & &str) -> Option<&'a T> {
+        self.find(key)
+    }
+}
+
+impl<T> PhfBiMap<T> {
+    /// Returns the index of the entry for `key`.
+    ///
+    /// See `PhfMap::index_of`.
+    #[inline]
+    pub fn index_of(&self, key: & &str) -> Option<uint> {
+        self.map.index_of(key)
+    }
+}
+
+forward_map_methods!(PhfBiMap, PhfMapEntries, PhfMapKeys, PhfMapValues)
+forward_prefix_methods!(map PhfBiMap)
+
+impl<T: PhfHash + PartialEq> PhfBiMap<T> {
+    fn find_entry_by_value(&self, value: &T)
+                           -> Option<&'static (&'static str, uint)> {
+        if self.disps.is_empty() || self.idxs.is_empty() {
+            return None;
+        }
+
+        let (g, f1, f2) = value.phf_hash(self.k1, self.k2);
+        let (d1, d2) = self.disps[g % self.disps.len()];
+        let idx = self.idxs[displace(f1, f2, d1, d2) % self.idxs.len()];
+        let entry @ &(_, value_idx) = &self.map.entries[idx];
+
+        if self.map.values[value_idx] == *value {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the key that maps to `value`.
+    pub fn find_by_value(&self, value: &T) -> Option<&'static str> {
+        self.find_entry_by_value(value).map(|&(s, _)| s)
+    }
 
-use std::fmt;
-use std::hash::Hasher;
-use std::hash::sip::SipHasher;
-use std::slice;
+    /// Returns true if some key maps to `value`.
+    #[inline]
+    pub fn contains_value(&self, value: &T) -> bool {
+        self.find_entry_by_value(value).is_some()
+    }
 
-/// An immutable map constructed at compile time.
+    /// Checks that every key and every value in the map can be found by a
+    /// lookup.
+    ///
+    /// Maps created by the `phf_bimap` macro always pass this check, but maps
+    /// written out by hand or by other tools may not.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        try!(self.map.validate());
+
+        if self.disps.is_empty() && !self.map.entries.is_empty() {
+            return Err(NoDisplacements);
+        }
+        if !self.disps.is_empty() && self.map.entries.is_empty() {
+            return Err(NoEntries);
+        }
+
+        if self.idxs.len() != self.map.entries.len() {
+            return Err(IndexCountMismatch(self.idxs.len(),
+                                          self.map.entries.len()));
+        }
+
+        for (slot, &idx) in self.idxs.iter().enumerate() {
+            if idx >= self.map.entries.len() {
+                return Err(IndexOutOfBounds(slot, idx));
+            }
+        }
+
+        for (i, &(key, value_idx)) in self.map.entries.iter().enumerate() {
+            let value = &self.map.values[value_idx];
+            let (g, f1, f2) = value.phf_hash(self.k1, self.k2);
+            let (d1, d2) = self.disps[g % self.disps.len()];
+            let found = self.idxs[displace(f1, f2, d1, d2) % self.idxs.len()];
+            if found != i {
+                return Err(MisplacedValue(key, i, found));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fails if `validate` reports an error.
+    ///
+    /// The check is skipped when compiled with `--cfg ndebug`.
+    pub fn debug_validate(&self) {
+        if cfg!(not(ndebug)) {
+            match self.validate() {
+                Ok(()) => {}
+                Err(err) => fail!("invalid PhfBiMap: {}", err),
+            }
+        }
+    }
+}
+
+/// An immutable map constructed at compile time in which each key has a list
+/// of values.
 ///
-/// `PhfMap`s may be created with the `phf_map` macro:
+/// A key may be listed several times in a `phf_multimap!` invocation; its
+/// values are grouped together in the order they are given. The
+/// `on_duplicate` option is therefore rejected.
 ///
 /// ```rust
 /// # #![feature(phase)]
@@ -20,67 +2388,33 @@ use std::slice;
 /// #[phase(syntax)]
 /// extern crate phf_mac;
 ///
-/// use phf::PhfMap;
+/// use phf::PhfMultiMap;
 ///
-/// static MY_MAP: PhfMap<int> = phf_map! {
-///    "hello" => 10,
-///    "world" => 11,
+/// static EXTENSIONS: PhfMultiMap<&'static str> = phf_multimap! {
+///    "image/jpeg" => "jpg",
+///    "image/jpeg" => "jpeg",
+///    "image/png" => "png",
 /// };
 ///
-/// # fn main() {}
+/// fn main() {
+///     let jpeg = EXTENSIONS.find(&"image/jpeg").unwrap();
+///     assert_eq!(vec!["jpg", "jpeg"], Vec::from_slice(jpeg));
+/// }
 /// ```
 ///
 /// # Note
 ///
 /// The fields of this struct are public so that they may be initialized by the
-/// `phf_map` macro. They are subject to change at any time and should never
-/// be accessed directly.
-pub struct PhfMap<T> {
-    #[doc(hidden)]
-    pub k1: u64,
-    #[doc(hidden)]
-    pub k2: u64,
+/// `phf_multimap` macro. They are subject to change at any time and should
+/// never be accessed directly.
+pub struct PhfMultiMap<T> {
     #[doc(hidden)]
-    pub disps: &'static [(uint, uint)],
+    pub map: PhfMap<(uint, uint)>,
     #[doc(hidden)]
-    pub entries: &'static [(&'static str, T)],
-}
-
-static LOG_MAX_SIZE: uint = 21;
-
-#[doc(hidden)]
-pub static MAX_SIZE: uint = 1 << LOG_MAX_SIZE;
-
-#[doc(hidden)]
-#[inline]
-pub fn hash(s: &str, k1: u64, k2: u64) -> (uint, uint, uint) {
-    let hash = SipHasher::new_with_keys(k1, k2).hash(&s);
-    let mask = (MAX_SIZE - 1) as u64;
-
-    ((hash & mask) as uint,
-     ((hash >> LOG_MAX_SIZE) & mask) as uint,
-     ((hash >> (2 * LOG_MAX_SIZE)) & mask) as uint)
-}
-
-#[doc(hidden)]
-#[inline]
-pub fn displace(f1: uint, f2: uint, d1: uint, d2: uint) -> uint {
-    d2 + f1 * d1 + f2
-}
-
-impl<T> Container for PhfMap<T> {
-    fn len(&self) -> uint {
-        self.entries.len()
-    }
-}
-
-impl<'a, T> Map<&'a str, T> for PhfMap<T> {
-    fn find<'a>(&'a self, key: & &str) -> Option<&'a T> {
-        self.find_entry(key).map(|&(_, ref v)| v)
-    }
+    pub values: &'static [T],
 }
 
-impl<T: fmt::Show> fmt::Show for PhfMap<T> {
+impl<T: fmt::Show> fmt::Show for PhfMultiMap<T> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         try!(write!(fmt, r"\{"));
         let mut first = true;
@@ -95,72 +2429,213 @@ impl<T: fmt::Show> fmt::Show for PhfMap<T> {
     }
 }
 
-impl<T> PhfMap<T> {
-    fn find_entry(&self, key: & &str) -> Option<&'static (&'static str, T)> {
-        let (g, f1, f2) = hash(*key, self.k1, self.k2);
-        let (d1, d2) = self.disps[g % self.disps.len()];
-        let entry @ &(s, _) = &self.entries[displace(f1, f2, d1, d2) %
-                                            self.entries.len()];
-        if s == *key {
-            Some(entry)
-        } else {
-            None
-        }
+#[cfg(feature = "std")]
+impl<T> Container for PhfMultiMap<T> {
+    #[inline]
+    fn len(&self) -> uint {
+        self.len()
+    }
+}
+
+impl<T> PhfMultiMap<T> {
+    /// Returns the number of distinct keys in the map.
+    #[inline]
+    pub fn len(&self) -> uint {
+        self.map.len()
+    }
+
+    /// Returns true if the map contains no keys.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the values that `key` maps to.
+    pub fn find<'a>(&'a self, key: & &str) -> Option<&'a [T]> {
+        self.map.find(key).map(|&(start, end)| self.values.slice(start, end))
+    }
+
+    /// Returns the values that a prehashed key maps to.
+    ///
+    /// See `PhfMap::find_prehashed`.
+    pub fn find_prehashed<'a>(&'a self, key: &PhfKey) -> Option<&'a [T]> {
+        self.map.find_prehashed(key).map(|&(start, end)| {
+            self.values.slice(start, end)
+        })
+    }
+
+    /// Looks up each of `keys`, storing the values it maps to at the same
+    /// position in `values`.
+    ///
+    /// See `PhfMap::find_many`.
+    pub fn find_many<'a>(&'a self, keys: &[&str],
+                         values: &mut [Option<&'a [T]>]) {
+        let map = &self.map;
+        find_many_batched(map.k1, map.k2, map.disps, map.entries.len(),
+                          map.entries, keys, values, |slot| slot, |idx| {
+            let (start, end) = map.values[idx];
+            self.values.slice(start, end)
+        })
+    }
+
+    /// Returns true if the map contains values for `key`.
+    #[inline]
+    pub fn contains_key(&self, key: & &str) -> bool {
+        self.map.contains_key(key)
     }
 
     /// Returns a reference to the map's internal static instance of the given
     /// key.
     ///
     /// This can be useful for interning schemes.
+    #[inline]
     pub fn find_key(&self, key: & &str) -> Option<&'static str> {
-        self.find_entry(key).map(|&(s, _)| s)
+        self.map.find_key(key)
     }
 
-    /// Returns an iterator over the key/value pairs in the map.
+    /// Returns the index of the entry for `key`.
     ///
-    /// Entries are retuned in an arbitrary but fixed order.
-    pub fn entries<'a>(&'a self) -> PhfMapEntries<'a, T> {
-        PhfMapEntries { iter: self.entries.iter() }
+    /// See `PhfMap::index_of`.
+    #[inline]
+    pub fn index_of(&self, key: & &str) -> Option<uint> {
+        self.map.index_of(key)
     }
 
-    /// Returns an iterator over the keys in the map.
+    /// Returns the index of the entry for a prehashed key.
     ///
-    /// Keys are returned in an arbitrary but fixed order.
-    pub fn keys<'a>(&'a self) -> PhfMapKeys<'a, T> {
-        PhfMapKeys { iter: self.entries() }
+    /// See `PhfMap::find_prehashed`.
+    #[inline]
+    pub fn index_of_prehashed(&self, key: &PhfKey) -> Option<uint> {
+        self.map.index_of_prehashed(key)
     }
 
-    /// Returns an iterator over the values in the map.
+    /// Returns the key of the entry at `idx`.
+    #[inline]
+    pub fn key_at(&self, idx: uint) -> Option<&'static str> {
+        self.map.key_at(idx)
+    }
+
+    /// Returns the key of the entry at `idx` along with its values.
+    pub fn entry_at<'a>(&'a self, idx: uint)
+                        -> Option<(&'static str, &'a [T])> {
+        self.map.entry_at(idx).map(|(key, &(start, end))| {
+            (key, self.values.slice(start, end))
+        })
+    }
+
+    /// Returns the key which is the longest prefix of `s`, along with its
+    /// values.
     ///
-    /// Values are returned in an arbitrary but fixed order.
-    pub fn values<'a>(&'a self) -> PhfMapValues<'a, T> {
-        PhfMapValues { iter: self.entries() }
+    /// See `PhfMap::find_longest_prefix`.
+    pub fn find_longest_prefix<'a>(&'a self, s: &str)
+                                   -> Option<(&'static str, &'a [T])> {
+        self.map.find_longest_prefix(s).map(|(key, &(start, end))| {
+            (key, self.values.slice(start, end))
+        })
+    }
+
+    /// Returns the keys within `max_distance` edits of `key`, closest first.
+    ///
+    /// See `PhfMap::suggest`.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn suggest(&self, key: &str, max_distance: uint) -> Vec<&'static str> {
+        self.map.suggest(key, max_distance)
+    }
+
+    /// Checks that every key in the map can be found by a lookup.
+    ///
+    /// Maps created by the `phf_multimap` macro always pass this check, but
+    /// maps written out by hand or by other tools may not.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        try!(self.map.validate());
+
+        for (key, &(start, end)) in self.map.entries() {
+            if end > self.values.len() {
+                return Err(ValueOutOfBounds(key, end - 1));
+            }
+            if start > end {
+                return Err(ValueOutOfBounds(key, start));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fails if `validate` reports an error.
+    ///
+    /// The check is skipped when compiled with `--cfg ndebug`.
+    pub fn debug_validate(&self) {
+        if cfg!(not(ndebug)) {
+            match self.validate() {
+                Ok(()) => {}
+                Err(err) => fail!("invalid PhfMultiMap: {}", err),
+            }
+        }
+    }
+
+    /// Returns an iterator over the key/value pairs in the map, with one pair
+    /// for each value.
+    ///
+    /// Keys are returned in an arbitrary but fixed order, and the values of a
+    /// key in the order they were given.
+    pub fn entries<'a>(&'a self) -> PhfMultiMapEntries<'a, T> {
+        PhfMultiMapEntries {
+            iter: self.map.entries(),
+            values: self.values,
+            key: "",
+            group: self.values.slice(0, 0).iter(),
+        }
+    }
+
+    /// Returns an iterator over the distinct keys in the map.
+    ///
+    /// Keys are returned in an arbitrary but fixed order.
+    pub fn keys<'a>(&'a self) -> PhfMultiMapKeys<'a> {
+        PhfMultiMapKeys { iter: self.map.keys() }
     }
 }
 
-/// An iterator over the key/value pairs in a `PhfMap`.
-pub struct PhfMapEntries<'a, T> {
-    iter: slice::Items<'a, (&'static str, T)>,
+/// An iterator over the key/value pairs in a `PhfMultiMap`.
+pub struct PhfMultiMapEntries<'a, T> {
+    iter: PhfMapEntries<'a, (uint, uint)>,
+    values: &'a [T],
+    key: &'static str,
+    group: slice::Items<'a, T>,
 }
 
-impl<'a, T> Iterator<(&'static str, &'a T)> for PhfMapEntries<'a, T> {
+impl<'a, T> Iterator<(&'static str, &'a T)> for PhfMultiMapEntries<'a, T> {
     fn next(&mut self) -> Option<(&'static str, &'a T)> {
-        self.iter.next().map(|&(key, ref value)| (key, value))
+        loop {
+            match self.group.next() {
+                Some(value) => return Some((self.key, value)),
+                None => {}
+            }
+
+            match self.iter.next() {
+                Some((key, &(start, end))) => {
+                    self.key = key;
+                    self.group = self.values.slice(start, end).iter();
+                }
+                None => return None,
+            }
+        }
     }
 
     fn size_hint(&self) -> (uint, Option<uint>) {
-        self.iter.size_hint()
+        let (len, _) = self.group.size_hint();
+        (len, None)
     }
 }
 
-/// An iterator over the keys in a `PhfMap`.
-pub struct PhfMapKeys<'a, T> {
-    iter: PhfMapEntries<'a, T>,
+/// An iterator over the keys in a `PhfMultiMap`.
+pub struct PhfMultiMapKeys<'a> {
+    iter: PhfMapKeys<'a, (uint, uint)>,
 }
 
-impl<'a, T> Iterator<&'static str> for PhfMapKeys<'a, T> {
+impl<'a> Iterator<&'static str> for PhfMultiMapKeys<'a> {
     fn next(&mut self) -> Option<&'static str> {
-        self.iter.next().map(|(key, _)| key)
+        self.iter.next()
     }
 
     fn size_hint(&self) -> (uint, Option<uint>) {
@@ -168,24 +2643,161 @@ impl<'a, T> Iterator<&'static str> for PhfMapKeys<'a, T> {
     }
 }
 
-/// An iterator over the values in a `PhfMap`.
-pub struct PhfMapValues<'a, T> {
-    iter: PhfMapEntries<'a, T>,
+/// An immutable map constructed at compile time, with its entries sorted by
+/// key.
+///
+/// Exact lookups use a perfect hash function like the other maps, while
+/// `range` and `prefix` use binary searches over the sorted entries. Keys are
+/// compared byte by byte, as `&str`s are.
+///
+/// ```rust
+/// # #![feature(phase)]
+/// extern crate phf;
+/// #[phase(syntax)]
+/// extern crate phf_mac;
+///
+/// use phf::PhfSortedMap;
+///
+/// static COMMANDS: PhfSortedMap<uint> = phf_sorted_map! {
+///    "push" => 2,
+///    "pull" => 1,
+///    "commit" => 0,
+/// };
+///
+/// fn main() {
+///     let keys = COMMANDS.prefix("pu").map(|(k, _)| k).collect::<Vec<_>>();
+///     assert_eq!(vec!["pull", "push"], keys);
+/// }
+/// ```
+///
+/// # Note
+///
+/// The fields of this struct are public so that they may be initialized by the
+/// `phf_sorted_map` macro. They are subject to change at any time and should
+/// never be accessed directly.
+pub struct PhfSortedMap<T> {
+    #[doc(hidden)]
+    pub map: PhfOrderedMap<T>,
 }
 
-impl<'a, T> Iterator<&'a T> for PhfMapValues<'a, T> {
-    fn next(&mut self) -> Option<&'a T> {
-        self.iter.next().map(|(_, value)| value)
+impl<T: fmt::Show> fmt::Show for PhfSortedMap<T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        self.map.fmt(fmt)
     }
+}
 
-    fn size_hint(&self) -> (uint, Option<uint>) {
-        self.iter.size_hint()
+#[cfg(feature = "std")]
+impl<T> Container for PhfSortedMap<T> {
+    #[inline]
+    fn len(&self) -> uint {
+        self.len()
     }
 }
 
-/// An immutable set constructed at compile time.
+#[cfg(feature = "std")]
+impl<'a, T> Map<&'a str, T> for PhfSortedMap<T> {
+    #[inline]
+    fn find<'a>(&'a self, key: & &str) -> Option<&'a T> {
+        self.find(key)
+    }
+}
+
+// Returns the index of the first entry for which `pred` is false. `pred` must
+// be true for some leading run of the entries and false for the rest.
+fn partition_point(entries: &[(&'static str, uint)], pred: |&str| -> bool)
+                   -> uint {
+    let mut lo = 0;
+    let mut hi = entries.len();
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        let (key, _) = entries[mid];
+        if pred(key) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    lo
+}
+
+impl<T> PhfSortedMap<T> {
+    /// Returns the index of the entry for `key`.
+    ///
+    /// Entries are indexed in sort order, so the entry with the smallest key
+    /// is at index 0 and the result is the number of keys less than `key`.
+    #[inline]
+    pub fn index_of(&self, key: & &str) -> Option<uint> {
+        self.map.index_of(key)
+    }
+
+    /// Checks that every key in the map can be found by a lookup and that the
+    /// entries are sorted.
+    ///
+    /// Maps created by the `phf_sorted_map` macro always pass this check, but
+    /// maps written out by hand or by other tools may not.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        try!(self.map.validate());
+
+        for pair in self.map.entries.windows(2) {
+            let (first, _) = pair[0];
+            let (second, _) = pair[1];
+            if first >= second {
+                return Err(OutOfOrder(first, second));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fails if `validate` reports an error.
+    ///
+    /// The check is skipped when compiled with `--cfg ndebug`.
+    pub fn debug_validate(&self) {
+        if cfg!(not(ndebug)) {
+            match self.validate() {
+                Ok(()) => {}
+                Err(err) => fail!("invalid PhfSortedMap: {}", err),
+            }
+        }
+    }
+
+    /// Returns an iterator over the entries with keys at least `start` and
+    /// less than `end`, in order.
+    pub fn range<'a>(&'a self, start: &str, end: &str)
+                     -> PhfOrderedMapEntries<'a, T> {
+        let entries = self.map.entries;
+        let lo = partition_point(entries, |key| key < start);
+        let hi = partition_point(entries, |key| key < end);
+        self.slice_entries(lo, if hi < lo { lo } else { hi })
+    }
+
+    /// Returns an iterator over the entries with keys starting with `prefix`,
+    /// in order.
+    pub fn prefix<'a>(&'a self, prefix: &str) -> PhfOrderedMapEntries<'a, T> {
+        let entries = self.map.entries;
+        let lo = partition_point(entries, |key| key < prefix);
+        let hi = partition_point(entries, |key| {
+            key < prefix || key.starts_with(prefix)
+        });
+        self.slice_entries(lo, hi)
+    }
+
+    fn slice_entries<'a>(&'a self, lo: uint, hi: uint)
+                         -> PhfOrderedMapEntries<'a, T> {
+        PhfOrderedMapEntries {
+            iter: self.map.entries.slice(lo, hi).iter(),
+            values: self.map.values,
+        }
+    }
+}
+
+forward_map_methods!(PhfSortedMap, PhfOrderedMapEntries, PhfOrderedMapKeys,
+                    PhfOrderedMapValues)
+forward_prefix_methods!(map PhfSortedMap)
+
+/// An immutable set constructed at compile time, with its values sorted.
 ///
-/// `PhfSet`s may be created with the `phf_set` macro:
+/// See `PhfSortedMap`.
 ///
 /// ```rust
 /// # #![feature(phase)]
@@ -193,27 +2805,31 @@ impl<'a, T> Iterator<&'a T> for PhfMapValues<'a, T> {
 /// #[phase(syntax)]
 /// extern crate phf_mac;
 ///
-/// use phf::PhfSet;
+/// use phf::PhfSortedSet;
 ///
-/// static MY_SET: PhfSet = phf_set! {
-///    "hello",
-///    "world",
+/// static WORDS: PhfSortedSet = phf_sorted_set! {
+///    "banana",
+///    "apple",
+///    "cherry",
 /// };
 ///
-/// # fn main() {}
+/// fn main() {
+///     let words = WORDS.range("b", "d").collect::<Vec<_>>();
+///     assert_eq!(vec!["banana", "cherry"], words);
+/// }
 /// ```
 ///
 /// # Note
 ///
 /// The fields of this struct are public so that they may be initialized by the
-/// `phf_set` macro. They are subject to change at any time and should never be
-/// accessed directly.
-pub struct PhfSet {
+/// `phf_sorted_set` macro. They are subject to change at any time and should
+/// never be accessed directly.
+pub struct PhfSortedSet {
     #[doc(hidden)]
-    pub map: PhfMap<()>
+    pub map: PhfSortedMap<()>,
 }
 
-impl fmt::Show for PhfSet {
+impl fmt::Show for PhfSortedSet {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         try!(write!(fmt, r"\{"));
         let mut first = true;
@@ -228,72 +2844,109 @@ impl fmt::Show for PhfSet {
     }
 }
 
-impl Container for PhfSet {
+#[cfg(feature = "std")]
+impl Container for PhfSortedSet {
     #[inline]
     fn len(&self) -> uint {
-        self.map.len()
+        self.len()
     }
 }
 
-impl<'a> Set<&'a str> for PhfSet {
+#[cfg(feature = "std")]
+impl<'a> Set<&'a str> for PhfSortedSet {
     #[inline]
     fn contains(&self, value: & &'a str) -> bool {
-        self.map.contains_key(value)
+        self.contains(value)
     }
 
     #[inline]
-    fn is_disjoint(&self, other: &PhfSet) -> bool {
+    fn is_disjoint(&self, other: &PhfSortedSet) -> bool {
+        self.is_disjoint(other)
+    }
+
+    #[inline]
+    fn is_subset(&self, other: &PhfSortedSet) -> bool {
+        self.is_subset(other)
+    }
+}
+
+impl PhfSortedSet {
+    /// Returns true if the set has no values in common with `other`.
+    #[inline]
+    pub fn is_disjoint(&self, other: &PhfSortedSet) -> bool {
         !self.iter().any(|value| other.contains(&value))
     }
 
+    /// Returns true if every value in the set is also in `other`.
     #[inline]
-    fn is_subset(&self, other: &PhfSet) -> bool {
+    pub fn is_subset(&self, other: &PhfSortedSet) -> bool {
         self.iter().all(|value| other.contains(&value))
     }
-}
 
-impl PhfSet {
-    /// Returns a reference to the set's internal static instance of the given
-    /// key.
-    ///
-    /// This can be useful for interning schemes.
+    /// Returns true if every value in `other` is also in the set.
     #[inline]
-    pub fn find_key(&self, key: & &str) -> Option<&'static str> {
-        self.map.find_key(key)
+    pub fn is_superset(&self, other: &PhfSortedSet) -> bool {
+        other.is_subset(self)
     }
 
-    /// Returns an iterator over the values in the set.
+    /// Returns the index of `value` in the set.
     ///
-    /// Values are returned in an arbitrary but fixed order.
+    /// Values are indexed in sort order, so the smallest value is at index 0.
+    /// See `PhfSortedMap::index_of`.
     #[inline]
-    pub fn iter<'a>(&'a self) -> PhfSetValues<'a> {
-        PhfSetValues { iter: self.map.keys() }
+    pub fn index_of(&self, value: & &str) -> Option<uint> {
+        self.map.index_of(value)
     }
-}
 
-/// An iterator over the values in a `PhfSet`.
-pub struct PhfSetValues<'a> {
-    iter: PhfMapKeys<'a, ()>,
-}
+    /// Returns an iterator over the values in the set, in order.
+    #[inline]
+    pub fn iter<'a>(&'a self) -> PhfOrderedSetValues<'a> {
+        PhfOrderedSetValues { iter: self.map.keys() }
+    }
 
-impl<'a> Iterator<&'static str> for PhfSetValues<'a> {
+    /// Returns an iterator over the values at least `start` and less than
+    /// `end`, in order.
     #[inline]
-    fn next(&mut self) -> Option<&'static str> {
-        self.iter.next()
+    pub fn range<'a>(&'a self, start: &str, end: &str)
+                     -> PhfOrderedSetValues<'a> {
+        PhfOrderedSetValues {
+            iter: PhfOrderedMapKeys { iter: self.map.range(start, end) },
+        }
     }
 
+    /// Returns an iterator over the values starting with `prefix`, in order.
     #[inline]
-    fn size_hint(&self) -> (uint, Option<uint>) {
-        self.iter.size_hint()
+    pub fn prefix<'a>(&'a self, prefix: &str) -> PhfOrderedSetValues<'a> {
+        PhfOrderedSetValues {
+            iter: PhfOrderedMapKeys { iter: self.map.prefix(prefix) },
+        }
     }
 }
 
-/// An order-preserving immutable map constructed at compile time.
-///
-/// Unlike a `PhfMap`, the order of entries in a `PhfOrderedMap` is guaranteed
-/// to be the order the entries were listed in.
+forward_set_methods!(PhfSortedSet)
+forward_prefix_methods!(set PhfSortedSet)
+
+/// How `PhfSearchMap::search` picks matches.
+#[deriving(PartialEq, Clone, Show)]
+pub enum SearchMode {
+    /// Matches don't overlap. Of the matches starting first the longest is
+    /// taken, and the search continues after its end.
+    LeftmostLongest,
+    /// As `LeftmostLongest`, but only matches which are neither preceded nor
+    /// followed by a word character are taken. Word characters are ASCII
+    /// letters, digits and `_`, as well as all non-ASCII characters.
+    WholeWords,
+}
+
+/// An immutable map constructed at compile time which can also find its keys
+/// in a text.
 ///
-/// `PhfOrderedMap`s may be created with the `phf_ordered_map` macro:
+/// Besides the perfect hash function, the map holds an Aho-Corasick automaton
+/// over its keys, so the time a search takes does not depend on the number of
+/// keys. The automaton restarts at the end of each match, and may reread up
+/// to the length of the longest key, so a search of a text of `n` bytes with
+/// `m` matches takes `O(n + m * k)` time, where `k` is the length of the
+/// longest key: `O(n * k)` in the worst case. Keys may not be empty.
 ///
 /// ```rust
 /// # #![feature(phase)]
@@ -301,244 +2954,229 @@ impl<'a> Iterator<&'static str> for PhfSetValues<'a> {
 /// #[phase(syntax)]
 /// extern crate phf_mac;
 ///
-/// use phf::PhfOrderedMap;
+/// use phf::{PhfSearchMap, WholeWords};
 ///
-/// static MY_MAP: PhfOrderedMap<int> = phf_ordered_map! {
-///    "hello" => 10,
-///    "world" => 11,
+/// static SECRETS: PhfSearchMap<&'static str> = phf_search_map! {
+///    "password" => "credential",
+///    "token" => "credential",
+///    "ssn" => "identifier",
 /// };
 ///
-/// # fn main() {}
+/// fn main() {
+///     let text = "password=hunter2 tokens ssn:123";
+///     let found = SECRETS.search(text, WholeWords)
+///                        .map(|(offset, key, _)| (offset, key))
+///                        .collect::<Vec<_>>();
+///     assert_eq!(vec![(0, "password"), (24, "ssn")], found);
+/// }
 /// ```
 ///
 /// # Note
 ///
 /// The fields of this struct are public so that they may be initialized by the
-/// `phf_ordered_map` macro. They are subject to change at any time and should
+/// `phf_search_map` macro. They are subject to change at any time and should
 /// never be accessed directly.
-pub struct PhfOrderedMap<T> {
-    #[doc(hidden)]
-    pub k1: u64,
+pub struct PhfSearchMap<T> {
     #[doc(hidden)]
-    pub k2: u64,
+    pub map: PhfMap<T>,
     #[doc(hidden)]
-    pub disps: &'static [(uint, uint)],
+    pub edges: &'static [(u8, uint)],
     #[doc(hidden)]
-    pub idxs: &'static [uint],
+    pub states: &'static [(uint, uint, uint, uint)],
     #[doc(hidden)]
-    pub entries: &'static [(&'static str, T)],
+    pub outputs: &'static [(uint, uint)],
 }
 
-impl<T: fmt::Show> fmt::Show for PhfOrderedMap<T> {
+impl<T: fmt::Show> fmt::Show for PhfSearchMap<T> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        try!(write!(fmt, r"\{"));
-        let mut first = true;
-        for (k, v) in self.entries() {
-            if !first {
-                try!(write!(fmt, ", "));
-            }
-            try!(write!(fmt, "{}: {}", k, v))
-            first = false;
-        }
-        write!(fmt, r"\}")
+        self.map.fmt(fmt)
     }
 }
 
-impl<T> Container for PhfOrderedMap<T> {
+#[cfg(feature = "std")]
+impl<T> Container for PhfSearchMap<T> {
+    #[inline]
     fn len(&self) -> uint {
-        self.entries.len()
+        self.len()
     }
 }
 
-impl<'a, T> Map<&'a str, T> for PhfOrderedMap<T> {
+#[cfg(feature = "std")]
+impl<'a, T> Map<&'a str, T> for PhfSearchMap<T> {
+    #[inline]
     fn find<'a>(&'a self, key: & &str) -> Option<&'a T> {
-        self.find_entry(key).map(|&(_, ref v)| v)
+        self.find(key)
     }
 }
 
-impl<T> PhfOrderedMap<T> {
-    fn find_entry(&self, key: & &str) -> Option<&'static (&'static str, T)> {
-        let (g, f1, f2) = hash(*key, self.k1, self.k2);
-        let (d1, d2) = self.disps[g % self.disps.len()];
-        let idx = self.idxs[displace(f1, f2, d1, d2) % self.idxs.len()];
-        let entry @ &(s, _) = &self.entries[idx];
-
-        if s == *key {
-            Some(entry)
-        } else {
-            None
-        }
-    }
+fn is_word_byte(b: u8) -> bool {
+    (b >= 'a' as u8 && b <= 'z' as u8) || (b >= 'A' as u8 && b <= 'Z' as u8) ||
+        (b >= '0' as u8 && b <= '9' as u8) || b == '_' as u8 || b >= 0x80
+}
 
-    /// Returns a reference to the map's internal static instance of the given
-    /// key.
+impl<T> PhfSearchMap<T> {
+    /// Returns the index of the entry for `key`.
     ///
-    /// This can be useful for interning schemes.
-    pub fn find_key(&self, key: & &str) -> Option<&'static str> {
-        self.find_entry(key).map(|&(s, _)| s)
+    /// See `PhfMap::index_of`.
+    #[inline]
+    pub fn index_of(&self, key: & &str) -> Option<uint> {
+        self.map.index_of(key)
     }
 
-    /// Returns an iterator over the key/value pairs in the map.
+    /// Checks that every key in the map can be found by a lookup.
     ///
-    /// Entries are retuned in the same order in which they were defined.
-    pub fn entries<'a>(&'a self) -> PhfOrderedMapEntries<'a, T> {
-        PhfOrderedMapEntries { iter: self.entries.iter() }
+    /// See `PhfMap::validate`.
+    #[inline]
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        self.map.validate()
     }
 
-    /// Returns an iterator over the keys in the map.
+    /// Fails if `validate` reports an error.
     ///
-    /// Keys are returned in the same order in which they were defined.
-    pub fn keys<'a>(&'a self) -> PhfOrderedMapKeys<'a, T> {
-        PhfOrderedMapKeys { iter: self.entries() }
+    /// The check is skipped when compiled with `--cfg ndebug`.
+    #[inline]
+    pub fn debug_validate(&self) {
+        self.map.debug_validate()
     }
 
-    /// Returns an iterator over the values in the map.
+    /// Returns an iterator over the occurrences of the map's keys in `text`.
     ///
-    /// Values are returned in the same order in which they were defined.
-    pub fn values<'a>(&'a self) -> PhfOrderedMapValues<'a, T> {
-        PhfOrderedMapValues { iter: self.entries() }
-    }
-}
-
-/// An iterator over the entries in a `PhfOrderedMap`.
-pub struct PhfOrderedMapEntries<'a, T> {
-    iter: slice::Items<'a, (&'static str, T)>,
-}
-
-impl<'a, T> Iterator<(&'static str, &'a T)> for PhfOrderedMapEntries<'a, T> {
-    fn next(&mut self) -> Option<(&'static str, &'a T)> {
-        self.iter.next().map(|&(key, ref value)| (key, value))
-    }
-
-    fn size_hint(&self) -> (uint, Option<uint>) {
-        self.iter.size_hint()
+    /// Each match is returned as its byte offset in `text`, the key and the
+    /// key's value, in order of offset.
+    #[inline]
+    pub fn search<'a, 'b>(&'a self, text: &'b str, mode: SearchMode)
+                          -> PhfSearchMatches<'a, 'b, T> {
+        self.search_bytes(text.as_bytes(), mode)
     }
-}
 
-impl<'a, T> DoubleEndedIterator<(&'static str, &'a T)>
-        for PhfOrderedMapEntries<'a, T> {
-    fn next_back(&mut self) -> Option<(&'static str, &'a T)> {
-        self.iter.next_back().map(|&(key, ref value)| (key, value))
+    /// Like `search`, but searches a byte string.
+    pub fn search_bytes<'a, 'b>(&'a self, text: &'b [u8], mode: SearchMode)
+                                -> PhfSearchMatches<'a, 'b, T> {
+        PhfSearchMatches {
+            map: self,
+            text: text,
+            pos: 0,
+            mode: mode,
+        }
     }
-}
 
-impl<'a, T> RandomAccessIterator<(&'static str, &'a T)>
-        for PhfOrderedMapEntries<'a, T> {
-    fn indexable(&self) -> uint {
-        self.iter.indexable()
-    }
+    // Follows the edge for `byte` out of `state`, falling back along failure
+    // links until one exists.
+    fn step(&self, mut state: uint, byte: u8) -> uint {
+        loop {
+            let (lo, hi, fail, _) = self.states[state];
+            let edges = self.edges.slice(lo, hi);
+            let mut lo = 0;
+            let mut hi = edges.len();
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                let (b, target) = edges[mid];
+                if b == byte {
+                    return target;
+                } else if b < byte {
+                    lo = mid + 1;
+                } else {
+                    hi = mid;
+                }
+            }
 
-    fn idx(&mut self, index: uint) -> Option<(&'static str, &'a T)> {
-        // FIXME: mozilla/rust#13167
-        self.iter.idx(index).map(|pair| {
-            let &(key, ref value) = pair;
-            (key, value)
-        })
+            if state == 0 {
+                return 0;
+            }
+            state = fail;
+        }
     }
 }
 
-impl<'a, T> ExactSize<(&'static str, &'a T)> for PhfOrderedMapEntries<'a, T> {}
-
-/// An iterator over the keys in a `PhfOrderedMap`.
-pub struct PhfOrderedMapKeys<'a, T> {
-    iter: PhfOrderedMapEntries<'a, T>,
-}
-
-impl<'a, T> Iterator<&'static str> for PhfOrderedMapKeys<'a, T> {
-    fn next(&mut self) -> Option<&'static str> {
-        self.iter.next().map(|(key, _)| key)
-    }
-
-    fn size_hint(&self) -> (uint, Option<uint>) {
-        self.iter.size_hint()
-    }
-}
+forward_map_methods!(PhfSearchMap, PhfMapEntries, PhfMapKeys, PhfMapValues)
 
-impl<'a, T> DoubleEndedIterator<&'static str> for PhfOrderedMapKeys<'a, T> {
-    fn next_back(&mut self) -> Option<&'static str> {
-        self.iter.next_back().map(|(key, _)| key)
-    }
+/// An iterator over the matches of a search of a `PhfSearchMap`.
+pub struct PhfSearchMatches<'a, 'b, T> {
+    map: &'a PhfSearchMap<T>,
+    text: &'b [u8],
+    pos: uint,
+    mode: SearchMode,
 }
 
-impl<'a, T> RandomAccessIterator<&'static str> for PhfOrderedMapKeys<'a, T> {
-    fn indexable(&self) -> uint {
-        self.iter.indexable()
-    }
-
-    fn idx(&mut self, index: uint) -> Option<&'static str> {
-        self.iter.idx(index).map(|(key, _)| key)
+impl<'a, 'b, T> PhfSearchMatches<'a, 'b, T> {
+    fn is_whole_word(&self, start: uint, end: uint) -> bool {
+        (start == 0 || !is_word_byte(self.text[start - 1])) &&
+            (end == self.text.len() || !is_word_byte(self.text[end]))
     }
 }
 
-impl<'a, T> ExactSize<&'static str> for PhfOrderedMapKeys<'a, T> {}
-
-/// An iterator over the values in a `PhfOrderedMap`.
-pub struct PhfOrderedMapValues<'a, T> {
-    iter: PhfOrderedMapEntries<'a, T>,
-}
-
-impl<'a, T> Iterator<&'a T> for PhfOrderedMapValues<'a, T> {
-    fn next(&mut self) -> Option<&'a T> {
-        self.iter.next().map(|(_, value)| value)
-    }
-
-    fn size_hint(&self) -> (uint, Option<uint>) {
-        self.iter.size_hint()
-    }
-}
+impl<'a, 'b, T> Iterator<(uint, &'static str, &'a T)>
+        for PhfSearchMatches<'a, 'b, T> {
+    fn next(&mut self) -> Option<(uint, &'static str, &'a T)> {
+        let map = self.map;
+        let mut state = 0;
+        // The start of the best match so far, and its entry
+        let mut best: Option<(uint, uint)> = None;
+
+        let mut i = self.pos;
+        while i < self.text.len() {
+            state = map.step(state, self.text[i]);
+            let (_, _, _, depth) = map.states[state];
+            let end = i + 1;
+
+            // Matches found from here on start at or after `end - depth`
+            match best {
+                Some((start, _)) if end - depth > start => break,
+                _ => {}
+            }
 
-impl<'a, T> DoubleEndedIterator<&'a T> for PhfOrderedMapValues<'a, T> {
-    fn next_back(&mut self) -> Option<&'a T> {
-        self.iter.next_back().map(|(_, value)| value)
-    }
-}
+            // Output states along the failure chain, longest key first
+            let (entry, next) = map.outputs[state];
+            let mut output = if entry == 0 { next } else { state };
+            while output != 0 {
+                let (entry, next) = map.outputs[output];
+                let (key, _) = map.map.entries[entry - 1];
+                let start = end - key.len();
+                let better = match best {
+                    Some((best_start, _)) => start <= best_start,
+                    None => true,
+                };
+                if better && (self.mode == LeftmostLongest ||
+                              self.is_whole_word(start, end)) {
+                    best = Some((start, entry - 1));
+                    break;
+                }
+                output = next;
+            }
 
-impl<'a, T> RandomAccessIterator<&'a T> for PhfOrderedMapValues<'a, T> {
-    fn indexable(&self) -> uint {
-        self.iter.indexable()
-    }
+            i += 1;
+        }
 
-    fn idx(&mut self, index: uint) -> Option<&'a T> {
-        self.iter.idx(index).map(|(_, value)| value)
+        match best {
+            Some((start, entry)) => {
+                let (key, idx) = map.map.entries[entry];
+                self.pos = start + key.len();
+                Some((start, key, &map.map.values[idx]))
+            }
+            None => {
+                self.pos = self.text.len();
+                None
+            }
+        }
     }
 }
 
-impl<'a, T> ExactSize<&'a T> for PhfOrderedMapValues<'a, T> {}
-
-/// An order-preserving immutable set constructed at compile time.
-///
-/// Unlike a `PhfSet`, the order of entries in a `PhfOrderedSet` is guaranteed
-/// to be the order the entries were listed in.
-///
-/// `PhfOrderedSet`s may be created with the `phf_ordered_set` macro:
-///
-/// ```rust
-/// # #![feature(phase)]
-/// extern crate phf;
-/// #[phase(syntax)]
-/// extern crate phf_mac;
-///
-/// use phf::PhfOrderedSet;
-///
-/// static MY_SET: PhfOrderedSet = phf_ordered_set! {
-///    "hello",
-///    "world",
-/// };
+/// An immutable set constructed at compile time which can also find its
+/// values in a text.
 ///
-/// # fn main() {}
-/// ```
+/// See `PhfSearchMap`.
 ///
 /// # Note
 ///
 /// The fields of this struct are public so that they may be initialized by the
-/// `phf_ordered_set` macro. They are subject to change at any time and should
+/// `phf_search_set` macro. They are subject to change at any time and should
 /// never be accessed directly.
-pub struct PhfOrderedSet {
+pub struct PhfSearchSet {
     #[doc(hidden)]
-    pub map: PhfOrderedMap<()>,
+    pub map: PhfSearchMap<()>,
 }
 
-impl fmt::Show for PhfOrderedSet {
+impl fmt::Show for PhfSearchSet {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         try!(write!(fmt, r"\{"));
         let mut first = true;
@@ -553,83 +3191,60 @@ impl fmt::Show for PhfOrderedSet {
     }
 }
 
-impl Container for PhfOrderedSet {
+#[cfg(feature = "std")]
+impl Container for PhfSearchSet {
     #[inline]
     fn len(&self) -> uint {
-        self.map.len()
-    }
-}
-
-impl<'a> Set<&'a str> for PhfOrderedSet {
-    #[inline]
-    fn contains(&self, value: & &'a str) -> bool {
-        self.map.contains_key(value)
-    }
-
-    #[inline]
-    fn is_disjoint(&self, other: &PhfOrderedSet) -> bool {
-        !self.iter().any(|value| other.contains(&value))
-    }
-
-    #[inline]
-    fn is_subset(&self, other: &PhfOrderedSet) -> bool {
-        self.iter().all(|value| other.contains(&value))
+        self.len()
     }
 }
 
-impl PhfOrderedSet {
-    /// Returns a reference to the set's internal static instance of the given
-    /// key.
+impl PhfSearchSet {
+    /// Returns the index of `value` in the set.
     ///
-    /// This can be useful for interning schemes.
+    /// See `PhfSearchMap::index_of`.
     #[inline]
-    pub fn find_key(&self, key: & &str) -> Option<&'static str> {
-        self.map.find_key(key)
+    pub fn index_of(&self, value: & &str) -> Option<uint> {
+        self.map.index_of(value)
     }
 
     /// Returns an iterator over the values in the set.
     ///
-    /// Values are returned in the same order in which they were defined.
+    /// Values are returned in an arbitrary but fixed order.
     #[inline]
-    pub fn iter<'a>(&'a self) -> PhfOrderedSetValues<'a> {
-        PhfOrderedSetValues { iter: self.map.keys() }
+    pub fn iter<'a>(&'a self) -> PhfSetValues<'a> {
+        PhfSetValues { iter: self.map.keys() }
     }
-}
-
-/// An iterator over the values in a `PhfOrderedSet`.
-pub struct PhfOrderedSetValues<'a> {
-    iter: PhfOrderedMapKeys<'a, ()>,
-}
 
-impl<'a> Iterator<&'static str> for PhfOrderedSetValues<'a> {
+    /// Returns an iterator over the occurrences of the set's values in
+    /// `text`.
+    ///
+    /// Each match is returned as its byte offset in `text` and the value, in
+    /// order of offset.
     #[inline]
-    fn next(&mut self) -> Option<&'static str> {
-        self.iter.next()
+    pub fn search<'a, 'b>(&'a self, text: &'b str, mode: SearchMode)
+                          -> PhfSearchSetMatches<'a, 'b> {
+        PhfSearchSetMatches { iter: self.map.search(text, mode) }
     }
 
+    /// Like `search`, but searches a byte string.
     #[inline]
-    fn size_hint(&self) -> (uint, Option<uint>) {
-        self.iter.size_hint()
+    pub fn search_bytes<'a, 'b>(&'a self, text: &'b [u8], mode: SearchMode)
+                                -> PhfSearchSetMatches<'a, 'b> {
+        PhfSearchSetMatches { iter: self.map.search_bytes(text, mode) }
     }
 }
 
-impl<'a> DoubleEndedIterator<&'static str> for PhfOrderedSetValues<'a> {
-    #[inline]
-    fn next_back(&mut self) -> Option<&'static str> {
-        self.iter.next_back()
-    }
-}
+forward_set_methods!(PhfSearchSet)
 
-impl<'a> RandomAccessIterator<&'static str> for PhfOrderedSetValues<'a> {
-    #[inline]
-    fn indexable(&self) -> uint {
-        self.iter.indexable()
-    }
+/// An iterator over the matches of a search of a `PhfSearchSet`.
+pub struct PhfSearchSetMatches<'a, 'b> {
+    iter: PhfSearchMatches<'a, 'b, ()>,
+}
 
+impl<'a, 'b> Iterator<(uint, &'static str)> for PhfSearchSetMatches<'a, 'b> {
     #[inline]
-    fn idx(&mut self, index: uint) -> Option<&'static str> {
-        self.iter.idx(index)
+    fn next(&mut self) -> Option<(uint, &'static str)> {
+        self.iter.next().map(|(offset, key, _)| (offset, key))
     }
 }
-
-impl<'a> ExactSize<&'static str> for PhfOrderedSetValues<'a> {}
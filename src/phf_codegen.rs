@@ -0,0 +1,438 @@
+//! Code generation for Rust-PHF
+//!
+//! This crate writes out the source of `phf` maps and sets so that they can be
+//! generated by build scripts and other tools. The generated expressions are
+//! plain constant expressions which may be used to initialize `static` items,
+//! so crates using them only need the `phf` crate and not the `phf_mac`
+//! compiler plugin.
+//!
+//! ```rust,no_run
+//! extern crate phf_codegen;
+//!
+//! use std::io::File;
+//!
+//! fn main() {
+//!     let mut file = File::create(&Path::new("keywords.rs")).unwrap();
+//!     write!(file, "static KEYWORDS: ::phf::PhfMap<Keyword> = ").unwrap();
+//!     phf_codegen::PhfMapBuilder::new()
+//!         .entry("loop", "LOOP")
+//!         .entry("continue", "CONTINUE")
+//!         .build(&mut file)
+//!         .unwrap();
+//!     write!(file, ";\n").unwrap();
+//! }
+//! ```
+#![crate_id="github.com/sfackler/rust-phf/phf_codegen"]
+#![doc(html_root_url="http://sfackler.github.io/rust-phf/doc")]
+#![crate_type="rlib"]
+#![crate_type="dylib"]
+#![warn(missing_doc)]
+
+extern crate rand;
+extern crate phf;
+
+use std::collections::{HashMap, HashSet};
+use std::io::IoResult;
+use rand::{Rng, SeedableRng, XorShiftRng};
+use phf::PhfHash;
+
+static DEFAULT_LAMBDA: uint = 5;
+
+static FIXED_SEED: [u32, ..4] = [3141592653, 589793238, 462643383, 2795028841];
+
+/// The tables of a perfect hash function over a list of keys.
+pub struct HashState {
+    /// The first SipHash key.
+    pub k1: u64,
+    /// The second SipHash key.
+    pub k2: u64,
+    /// The displacement table.
+    pub disps: Vec<(uint, uint)>,
+    /// The index of the key stored in each slot of the table.
+    pub map: Vec<uint>,
+}
+
+/// Generates a perfect hash function over `keys`.
+///
+/// The keys must be distinct. Generation is deterministic: the same keys will
+/// always produce the same tables.
+pub fn generate_hash<H: PhfHash>(keys: &[H]) -> HashState {
+    let mut rng: XorShiftRng = SeedableRng::from_seed(FIXED_SEED);
+    let buckets_len = (keys.len() + DEFAULT_LAMBDA - 1) / DEFAULT_LAMBDA;
+    loop {
+        let k1 = rng.gen();
+        let k2 = rng.gen();
+        match try_generate_hash(keys, k1, k2, buckets_len) {
+            Some(s) => return s,
+            None => {}
+        }
+    }
+}
+
+/// Generates a perfect hash function over `keys` with the SipHash keys given
+/// by `seed`.
+///
+/// Maps generated with the same seed hash a key in the same way, so a key
+/// hashed once with `phf::PhfKey::new` can be looked up in all of them.
+///
+/// The hash keys can't be changed if no displacements are found for them, so
+/// the number of buckets of keys is doubled instead, up to 64 per key. `None`
+/// is returned if that fails as well, in which case another seed should be
+/// used.
+pub fn generate_hash_with_seed<H: PhfHash>(keys: &[H], seed: u64)
+                                           -> Option<HashState> {
+    let (k1, k2) = phf::seed_keys(seed);
+    let mut buckets_len = (keys.len() + DEFAULT_LAMBDA - 1) / DEFAULT_LAMBDA;
+    while buckets_len <= 64 * keys.len() {
+        match try_generate_hash(keys, k1, k2, buckets_len) {
+            Some(s) => return Some(s),
+            None => {}
+        }
+        buckets_len *= 2;
+    }
+    None
+}
+
+fn try_generate_hash<H: PhfHash>(keys: &[H], k1: u64, k2: u64,
+                                 buckets_len: uint) -> Option<HashState> {
+    struct Bucket {
+        idx: uint,
+        keys: Vec<uint>,
+    }
+
+    struct Hashes {
+        g: uint,
+        f1: uint,
+        f2: uint,
+    }
+
+    let hashes: Vec<Hashes> = keys.iter().map(|key| {
+        let (g, f1, f2) = key.phf_hash(k1, k2);
+        Hashes {
+            g: g,
+            f1: f1,
+            f2: f2
+        }
+    }).collect();
+
+    let mut buckets = Vec::from_fn(buckets_len,
+                                   |i| Bucket { idx: i, keys: Vec::new() });
+
+    for (i, hash) in hashes.iter().enumerate() {
+        buckets.get_mut(hash.g % buckets_len).keys.push(i);
+    }
+
+    // Sort descending
+    buckets.sort_by(|a, b| b.keys.len().cmp(&a.keys.len()));
+
+    let table_len = keys.len();
+    let mut map = Vec::from_elem(table_len, None);
+    let mut disps = Vec::from_elem(buckets_len, (0u, 0u));
+    let mut try_map = HashMap::new();
+    'buckets: for bucket in buckets.iter() {
+        for d1 in range(0, table_len) {
+            'disps_l: for d2 in range(0, table_len) {
+                try_map.clear();
+                for &key in bucket.keys.iter() {
+                    let idx = phf::displace(hashes.get(key).f1,
+                                            hashes.get(key).f2,
+                                            d1,
+                                            d2) % table_len;
+                    if map.get(idx).is_some() || try_map.find(&idx).is_some() {
+                        continue 'disps_l;
+                    }
+                    try_map.insert(idx, key);
+                }
+
+                // We've picked a good set of disps
+                *disps.get_mut(bucket.idx) = (d1, d2);
+                for (&idx, &key) in try_map.iter() {
+                    *map.get_mut(idx) = Some(key);
+                }
+                continue 'buckets;
+            }
+        }
+
+        // Unable to find displacements for a bucket
+        return None;
+    }
+
+    Some(HashState {
+        k1: k1,
+        k2: k2,
+        disps: disps,
+        map: map.move_iter().map(|i| i.unwrap()).collect(),
+    })
+}
+
+fn check_duplicates(keys: &[&str]) {
+    let mut set = HashSet::new();
+    for &key in keys.iter() {
+        if !set.insert(key) {
+            fail!("duplicate key `{}`", key);
+        }
+    }
+}
+
+fn write_disps(w: &mut Writer, state: &HashState) -> IoResult<()> {
+    try!(write!(w, "    disps: &'static ["));
+    for &(d1, d2) in state.disps.iter() {
+        try!(write!(w, "\n        ({}, {}),", d1, d2));
+    }
+    write!(w, "\n    ],\n")
+}
+
+fn write_entry(w: &mut Writer, key: &str, value: uint) -> IoResult<()> {
+    write!(w, "\n        (\"{}\", {}),", key.escape_default(), value)
+}
+
+fn write_values(w: &mut Writer, values: &[String]) -> IoResult<()> {
+    try!(write!(w, "    values: &'static ["));
+    for value in values.iter() {
+        try!(write!(w, "\n        {},", value));
+    }
+    write!(w, "\n    ],\n")
+}
+
+// The table is left empty unless `longest_prefix` was called on the builder.
+fn write_lens(w: &mut Writer, keys: &[&str], longest_prefix: bool)
+              -> IoResult<()> {
+    let mut lens = if longest_prefix {
+        keys.iter().map(|k| k.len()).collect()
+    } else {
+        vec![]
+    };
+    lens.sort_by(|a: &uint, b| b.cmp(a));
+    lens.dedup();
+
+    try!(write!(w, "    lens: &'static ["));
+    for len in lens.iter() {
+        try!(write!(w, "\n        {},", len));
+    }
+    write!(w, "\n    ],\n")
+}
+
+fn write_len_idxs(w: &mut Writer, keys: &[&str], idxs: &[uint])
+                  -> IoResult<()> {
+    let key_lens = idxs.iter().map(|&idx| keys[idx].char_len())
+                       .collect::<Vec<uint>>();
+
+    let mut len_idxs = Vec::from_fn(idxs.len(), |i| i);
+    len_idxs.sort_by(|&a, &b| key_lens.get(a).cmp(key_lens.get(b)));
+
+    try!(write!(w, "    len_idxs: &'static ["));
+    for idx in len_idxs.iter() {
+        try!(write!(w, "\n        {},", idx));
+    }
+    try!(write!(w, "\n    ],\n"));
+
+    try!(write!(w, "    len_buckets: &'static ["));
+    for (i, &idx) in len_idxs.iter().enumerate() {
+        let len = *key_lens.get(idx);
+        let last = i + 1 == len_idxs.len() ||
+                   *key_lens.get(*len_idxs.get(i + 1)) != len;
+        if last {
+            try!(write!(w, "\n        ({}, {}),", len, i + 1));
+        }
+    }
+    write!(w, "\n    ],\n")
+}
+
+/// A builder for the source of a `PhfMap`.
+pub struct PhfMapBuilder {
+    keys: Vec<String>,
+    values: Vec<String>,
+    longest_prefix: bool,
+}
+
+impl PhfMapBuilder {
+    /// Creates a new builder with no entries.
+    pub fn new() -> PhfMapBuilder {
+        PhfMapBuilder {
+            keys: vec![],
+            values: vec![],
+            longest_prefix: false,
+        }
+    }
+
+    /// Stores the lengths of the keys, as the `longest_prefix` option of the
+    /// map macros does, so that `find_longest_prefix` makes one lookup for
+    /// each of them rather than for every prefix.
+    pub fn longest_prefix<'a>(&'a mut self) -> &'a mut PhfMapBuilder {
+        self.longest_prefix = true;
+        self
+    }
+
+    /// Adds an entry to the map.
+    ///
+    /// `value` is the source of a constant expression for the entry's value.
+    pub fn entry<'a>(&'a mut self, key: &str, value: &str)
+                     -> &'a mut PhfMapBuilder {
+        self.keys.push(key.to_string());
+        self.values.push(value.to_string());
+        self
+    }
+
+    /// Writes the source of a constant expression for the map to `w`.
+    ///
+    /// # Failure
+    ///
+    /// Fails if the same key was added more than once.
+    pub fn build(&self, w: &mut Writer) -> IoResult<()> {
+        let keys = self.keys.iter().map(|k| k.as_slice()).collect::<Vec<_>>();
+        check_duplicates(keys.as_slice());
+        let state = generate_hash(keys.as_slice());
+
+        try!(write!(w, "::phf::PhfMap \\{\n    k1: {},\n    k2: {},\n",
+                    state.k1, state.k2));
+        try!(write_disps(w, &state));
+        try!(write!(w, "    entries: &'static ["));
+        for &idx in state.map.iter() {
+            try!(write_entry(w, *keys.get(idx), idx));
+        }
+        try!(write!(w, "\n    ],\n"));
+        try!(write_values(w, self.values.as_slice()));
+        try!(write_lens(w, keys.as_slice(), self.longest_prefix));
+        try!(write_len_idxs(w, keys.as_slice(), state.map.as_slice()));
+        write!(w, "\\}")
+    }
+}
+
+/// A builder for the source of a `PhfSet`.
+pub struct PhfSetBuilder {
+    map: PhfMapBuilder,
+}
+
+impl PhfSetBuilder {
+    /// Creates a new builder with no entries.
+    pub fn new() -> PhfSetBuilder {
+        PhfSetBuilder { map: PhfMapBuilder::new() }
+    }
+
+    /// Stores the lengths of the keys, as the `longest_prefix` option of the
+    /// set macros does, so that `find_longest_prefix` makes one lookup for
+    /// each of them rather than for every prefix.
+    pub fn longest_prefix<'a>(&'a mut self) -> &'a mut PhfSetBuilder {
+        self.map.longest_prefix();
+        self
+    }
+
+    /// Adds an entry to the set.
+    pub fn entry<'a>(&'a mut self, key: &str) -> &'a mut PhfSetBuilder {
+        self.map.entry(key, "()");
+        self
+    }
+
+    /// Writes the source of a constant expression for the set to `w`.
+    ///
+    /// # Failure
+    ///
+    /// Fails if the same key was added more than once.
+    pub fn build(&self, w: &mut Writer) -> IoResult<()> {
+        try!(write!(w, "::phf::PhfSet \\{ map: "));
+        try!(self.map.build(w));
+        write!(w, " \\}")
+    }
+}
+
+/// A builder for the source of a `PhfOrderedMap`.
+pub struct PhfOrderedMapBuilder {
+    keys: Vec<String>,
+    values: Vec<String>,
+    longest_prefix: bool,
+}
+
+impl PhfOrderedMapBuilder {
+    /// Creates a new builder with no entries.
+    pub fn new() -> PhfOrderedMapBuilder {
+        PhfOrderedMapBuilder {
+            keys: vec![],
+            values: vec![],
+            longest_prefix: false,
+        }
+    }
+
+    /// Stores the lengths of the keys, as the `longest_prefix` option of the
+    /// map macros does, so that `find_longest_prefix` makes one lookup for
+    /// each of them rather than for every prefix.
+    pub fn longest_prefix<'a>(&'a mut self) -> &'a mut PhfOrderedMapBuilder {
+        self.longest_prefix = true;
+        self
+    }
+
+    /// Adds an entry to the map.
+    ///
+    /// `value` is the source of a constant expression for the entry's value.
+    pub fn entry<'a>(&'a mut self, key: &str, value: &str)
+                     -> &'a mut PhfOrderedMapBuilder {
+        self.keys.push(key.to_string());
+        self.values.push(value.to_string());
+        self
+    }
+
+    /// Writes the source of a constant expression for the map to `w`.
+    ///
+    /// # Failure
+    ///
+    /// Fails if the same key was added more than once.
+    pub fn build(&self, w: &mut Writer) -> IoResult<()> {
+        let keys = self.keys.iter().map(|k| k.as_slice()).collect::<Vec<_>>();
+        check_duplicates(keys.as_slice());
+        let state = generate_hash(keys.as_slice());
+
+        try!(write!(w, "::phf::PhfOrderedMap \\{\n    k1: {},\n    k2: {},\n",
+                    state.k1, state.k2));
+        try!(write_disps(w, &state));
+        try!(write!(w, "    idxs: &'static ["));
+        for &idx in state.map.iter() {
+            try!(write!(w, "\n        {},", idx));
+        }
+        try!(write!(w, "\n    ],\n    entries: &'static ["));
+        for (idx, key) in keys.iter().enumerate() {
+            try!(write_entry(w, *key, idx));
+        }
+        try!(write!(w, "\n    ],\n"));
+        try!(write_values(w, self.values.as_slice()));
+        try!(write_lens(w, keys.as_slice(), self.longest_prefix));
+        let order = Vec::from_fn(keys.len(), |i| i);
+        try!(write_len_idxs(w, keys.as_slice(), order.as_slice()));
+        write!(w, "\\}")
+    }
+}
+
+/// A builder for the source of a `PhfOrderedSet`.
+pub struct PhfOrderedSetBuilder {
+    map: PhfOrderedMapBuilder,
+}
+
+impl PhfOrderedSetBuilder {
+    /// Creates a new builder with no entries.
+    pub fn new() -> PhfOrderedSetBuilder {
+        PhfOrderedSetBuilder { map: PhfOrderedMapBuilder::new() }
+    }
+
+    /// Stores the lengths of the keys, as the `longest_prefix` option of the
+    /// set macros does, so that `find_longest_prefix` makes one lookup for
+    /// each of them rather than for every prefix.
+    pub fn longest_prefix<'a>(&'a mut self) -> &'a mut PhfOrderedSetBuilder {
+        self.map.longest_prefix();
+        self
+    }
+
+    /// Adds an entry to the set.
+    pub fn entry<'a>(&'a mut self, key: &str) -> &'a mut PhfOrderedSetBuilder {
+        self.map.entry(key, "()");
+        self
+    }
+
+    /// Writes the source of a constant expression for the set to `w`.
+    ///
+    /// # Failure
+    ///
+    /// Fails if the same key was added more than once.
+    pub fn build(&self, w: &mut Writer) -> IoResult<()> {
+        try!(write!(w, "::phf::PhfOrderedSet \\{ map: "));
+        try!(self.map.build(w));
+        write!(w, " \\}")
+    }
+}
@@ -6,35 +6,47 @@
 #![doc(html_root_url="http://sfackler.github.io/rust-phf/doc")]
 #![feature(managed_boxes, macro_registrar, quote)]
 
-extern crate rand;
 extern crate syntax;
 extern crate time;
 extern crate phf;
+extern crate phf_codegen;
 
-use std::collections::HashMap;
+use std::cell::RefCell;
+use std::collections::{HashMap, RingBuf};
 use std::os;
 use syntax::ast;
-use syntax::ast::{Name, TokenTree, LitStr, Expr, ExprVec, ExprLit};
-use syntax::codemap::Span;
+use syntax::ast::{Name, TokenTree, TTTok, LitStr, Expr, ExprVec, ExprLit, Item,
+                  Attribute, ExprBinary, BiBitOr, ExprPath, ExprMac, MacInvocTT,
+                  MetaItem, ItemStatic, LitInt, LitIntUnsuffixed, LitUint,
+                  LitChar, LitBool, ExprUnary, UnNeg};
+use syntax::attr;
+use syntax::attr::AttrMetaMethods;
+use syntax::codemap::{Span, Spanned, mk_sp};
 use syntax::ext::base::{SyntaxExtension,
                         DummyResult,
                         ExtCtxt,
+                        ItemDecorator,
                         MacResult,
                         MacExpr,
                         NormalTT,
                         BasicMacroExpander};
+use syntax::ext::build::AstBuilder;
 use syntax::parse;
+use syntax::parse::classify;
+use syntax::parse::common::seq_sep_none;
+use syntax::parse::parser::Parser;
 use syntax::parse::token;
-use syntax::parse::token::{InternedString, COMMA, EOF, FAT_ARROW};
-use rand::{Rng, SeedableRng, XorShiftRng};
-
-static DEFAULT_LAMBDA: uint = 5;
-
-static FIXED_SEED: [u32, ..4] = [3141592653, 589793238, 462643383, 2795028841];
+use syntax::parse::token::{InternedString, keywords, COLON, COMMA, DOC_COMMENT,
+                           EOF, EQ, FAT_ARROW, IDENT, LBRACE, LIT_STR, NOT,
+                           POUND, RBRACE, SEMI, UNDERSCORE};
+use syntax::util::small_vector::SmallVector;
+use phf_codegen::HashState;
 
 #[macro_registrar]
 #[doc(hidden)]
 pub fn macro_registrar(register: |Name, SyntaxExtension|) {
+    register(token::intern("phf_key"), ItemDecorator(expand_phf_key));
+
     let reg = |name, fn_| {
         register(token::intern(name),
                  NormalTT(box BasicMacroExpander {
@@ -47,110 +59,1440 @@ pub fn macro_registrar(register: |Name, SyntaxExtension|) {
     reg("phf_set", expand_phf_set);
     reg("phf_ordered_map", expand_phf_ordered_map);
     reg("phf_ordered_set", expand_phf_ordered_set);
+    reg("phf_bimap", expand_phf_bimap);
+    reg("phf_multimap", expand_phf_multimap);
+    reg("phf_sorted_map", expand_phf_sorted_map);
+    reg("phf_sorted_set", expand_phf_sorted_set);
+    reg("phf_search_map", expand_phf_search_map);
+    reg("phf_search_set", expand_phf_search_set);
+    reg("phf_get", expand_phf_get);
+    reg("phf_match", expand_phf_match);
+    reg("phf_enum", expand_phf_enum);
+    reg("phf_atom", expand_phf_atom);
+    reg("phf_atom_set", expand_phf_atom_set);
+    reg("phf_table", expand_phf_table);
+}
+
+// Information about the crate being compiled, collected as its items are
+// expanded.
+struct Registry {
+    // The values of `#[phf_key]` statics by path, or `None` if several statics
+    // with the same path have different values. Statics declared inside
+    // functions share the path of the enclosing module.
+    keys: HashMap<String, Option<InternedString>>,
+    // The maps declared with the item form by path, or `None` if several maps
+    // have the same path.
+    maps: HashMap<String, Option<MapInfo>>,
+    // The path of the static set of each atom type declared with
+    // `phf_atom_set!`, by the path of the type, or `None` if several types
+    // have the same path.
+    atom_sets: HashMap<String, Option<String>>,
+}
+
+// The layout of a map declared with the item form
+#[deriving(Clone)]
+struct MapInfo {
+    kind: Kind,
+    // The index into the map's values of the value for each key
+    values: HashMap<String, uint>,
+    // The index of the entry for each key, as returned by `index_of`
+    indices: HashMap<String, uint>,
+}
+
+local_data_key!(registry: RefCell<Registry>)
+
+fn with_registry<T>(f: |&mut Registry| -> T) -> T {
+    if registry.get().is_none() {
+        registry.replace(Some(RefCell::new(Registry {
+            keys: HashMap::new(),
+            maps: HashMap::new(),
+            atom_sets: HashMap::new(),
+        })));
+    }
+
+    let cell = registry.get().unwrap();
+    let mut borrow = cell.borrow_mut();
+    f(&mut *borrow)
+}
+
+fn expand_phf_key(cx: &mut ExtCtxt, sp: Span, _: @MetaItem, item: @Item,
+                  _: |@Item|) {
+    let expr = match item.node {
+        ItemStatic(_, _, expr) => expr,
+        _ => {
+            cx.span_err(sp, "`#[phf_key]` may only be applied to statics");
+            return;
+        }
+    };
+
+    let expr = cx.expand_expr(expr);
+    let value = match parse_str(cx, expr) {
+        Some(value) => value,
+        None => return,
+    };
+
+    let name = item_path(cx, item.ident);
+    with_registry(|registry| {
+        let ambiguous = match registry.keys.find(&name) {
+            Some(&Some(ref other)) => *other != value,
+            Some(&None) => true,
+            None => false,
+        };
+        let value = if ambiguous { None } else { Some(value.clone()) };
+        registry.keys.insert(name.clone(), value);
+    });
+}
+
+// The path of an item declared in the module being expanded, starting with the
+// name of the crate.
+fn item_path(cx: &ExtCtxt, ident: ast::Ident) -> String {
+    let mut path = cx.mod_path();
+    path.push(ident);
+    join_path(path.as_slice())
+}
+
+fn join_path(path: &[ast::Ident]) -> String {
+    path.iter().map(|&ident| token::get_ident(ident).get().to_string())
+        .collect::<Vec<String>>().connect("::")
+}
+
+// Resolves a path relative to the module being expanded to the path of the
+// item it refers to, starting with the name of the crate. Imports are not
+// followed, so only paths through modules, `self` and `super` resolve.
+fn resolve_path(cx: &ExtCtxt, path: &ast::Path) -> Option<Vec<ast::Ident>> {
+    let mod_path = cx.mod_path();
+    let mut resolved = if path.global {
+        vec![*mod_path.get(0)]
+    } else {
+        mod_path
+    };
+
+    let mut leading = true;
+    for segment in path.segments.iter() {
+        if !segment.lifetimes.is_empty() || !segment.types.is_empty() {
+            return None;
+        }
+
+        match token::get_ident(segment.identifier).get() {
+            "self" if leading && !path.global => {}
+            "super" if leading && !path.global => {
+                // The first element is the crate, which has no parent
+                if resolved.len() == 1 {
+                    return None;
+                }
+                resolved.pop();
+            }
+            _ => {
+                leading = false;
+                resolved.push(segment.identifier);
+            }
+        }
+    }
+
+    Some(resolved)
+}
+
+// Looks up the value of the `#[phf_key]` static with the given path.
+fn find_key(cx: &mut ExtCtxt, sp: Span, name: Option<String>, shown: &str)
+            -> Option<InternedString> {
+    let value = match name {
+        Some(ref name) => with_registry(|registry| {
+            registry.keys.find(name).map(|value| value.clone())
+        }),
+        None => None,
+    };
+
+    match value {
+        Some(Some(value)) => Some(value),
+        Some(None) => {
+            cx.span_err(sp, format!("`{}` refers to more than one `#[phf_key]` \
+                                     static", shown).as_slice());
+            None
+        }
+        None => {
+            cx.span_err(sp, format!("`{}` is not a `#[phf_key]` static declared \
+                                     earlier in this crate", shown).as_slice());
+            None
+        }
+    }
+}
+
+// Replaces references to `#[phf_key]` statics with their values, both as a
+// key and as an argument of `concat!`. Reports references which do not resolve
+// to such a static.
+fn resolve_key(cx: &mut ExtCtxt, e: @Expr) -> Option<@Expr> {
+    match e.node {
+        ExprPath(ref path) => {
+            let name = resolve_path(cx, path).map(|path| {
+                join_path(path.as_slice())
+            });
+            let segments = path.segments.iter().map(|segment| {
+                segment.identifier
+            }).collect::<Vec<ast::Ident>>();
+            let shown = join_path(segments.as_slice());
+            find_key(cx, e.span, name, shown.as_slice()).map(|value| {
+                cx.expr_str(e.span, value)
+            })
+        }
+        ExprMac(ref mac) => {
+            let (path, tts, ctxt) = match mac.node {
+                MacInvocTT(ref path, ref tts, ctxt) => (path, tts, ctxt),
+            };
+            if path.segments.len() != 1 ||
+                    token::get_ident(path.segments.get(0).identifier).get() !=
+                        "concat" {
+                return Some(e);
+            }
+
+            let mut bad = false;
+            let tts = tts.iter().map(|tt| {
+                match *tt {
+                    // `true` and `false` are literals to `concat!`
+                    TTTok(sp, IDENT(ident, false))
+                            if !token::is_any_keyword(&IDENT(ident, false)) => {
+                        let name = Some(item_path(cx, ident));
+                        let shown = token::get_ident(ident);
+                        match find_key(cx, sp, name, shown.get()) {
+                            Some(value) => {
+                                let lit = value.get().escape_default();
+                                let lit = token::intern(lit.as_slice());
+                                TTTok(sp, LIT_STR(lit))
+                            }
+                            None => {
+                                bad = true;
+                                tt.clone()
+                            }
+                        }
+                    }
+                    _ => tt.clone(),
+                }
+            }).collect();
+            if bad {
+                return None;
+            }
+
+            Some(@Expr {
+                node: ExprMac(Spanned {
+                    node: MacInvocTT(path.clone(), tts, ctxt),
+                    span: mac.span,
+                }),
+                ..(*e).clone()
+            })
+        }
+        _ => Some(e),
+    }
 }
 
 struct Entry {
     key_str: InternedString,
     key: @Expr,
-    value: @Expr
+    value: uint,
+}
+
+// Keys separated by `|` share one value, so values are stored apart from the
+// entries which refer to them.
+struct ParsedMap {
+    entries: Vec<Entry>,
+    values: Vec<@Expr>,
+    options: Options,
+}
+
+#[deriving(PartialEq)]
+enum DuplicatePolicy {
+    ErrorOnDuplicate,
+    KeepFirst,
+    KeepLast,
+}
+
+// Set with inner attributes at the start of an invocation
+struct Options {
+    on_duplicate: DuplicatePolicy,
+    // Where `on_duplicate` was given, if it was
+    on_duplicate_span: Option<Span>,
+    // Fixes the SipHash keys so that `PhfKey`s can be shared between tables
+    seed: Option<u64>,
+    // Where `longest_prefix` was given, if it was. The table of key lengths
+    // used by `find_longest_prefix` is only built if it was, and the method
+    // tries every prefix otherwise.
+    longest_prefix: Option<Span>,
+}
+
+impl Options {
+    fn new() -> Options {
+        Options {
+            on_duplicate: ErrorOnDuplicate,
+            on_duplicate_span: None,
+            seed: None,
+            longest_prefix: None,
+        }
+    }
+}
+
+#[deriving(PartialEq, Clone)]
+enum Kind {
+    MapKind,
+    SetKind,
+    OrderedMapKind,
+    OrderedSetKind,
+    BiMapKind,
+    MultiMapKind,
+    SortedMapKind,
+    SortedSetKind,
+    SearchMapKind,
+    SearchSetKind,
+}
+
+impl Kind {
+    fn has_values(&self) -> bool {
+        match *self {
+            MapKind | OrderedMapKind | BiMapKind | MultiMapKind |
+                SortedMapKind | SearchMapKind => true,
+            SetKind | OrderedSetKind | SortedSetKind | SearchSetKind => false,
+        }
+    }
+}
+
+struct MacItems {
+    items: Vec<@Item>,
 }
 
-struct HashState {
-    k1: u64,
-    k2: u64,
-    disps: Vec<(uint, uint)>,
-    map: Vec<uint>,
+impl MacResult for MacItems {
+    fn make_items(&self) -> Option<SmallVector<@Item>> {
+        Some(SmallVector::many(self.items.clone()))
+    }
 }
 
 fn expand_phf_map(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
                   -> Box<MacResult> {
-    let entries = match parse_map(cx, tts) {
-        Some(entries) => entries,
-        None => return DummyResult::expr(sp)
+    expand(cx, sp, tts, MapKind)
+}
+
+fn expand_phf_set(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                  -> Box<MacResult> {
+    expand(cx, sp, tts, SetKind)
+}
+
+fn expand_phf_ordered_map(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                          -> Box<MacResult> {
+    expand(cx, sp, tts, OrderedMapKind)
+}
+
+fn expand_phf_ordered_set(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                          -> Box<MacResult> {
+    expand(cx, sp, tts, OrderedSetKind)
+}
+
+fn expand_phf_bimap(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                    -> Box<MacResult> {
+    expand(cx, sp, tts, BiMapKind)
+}
+
+fn expand_phf_multimap(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                       -> Box<MacResult> {
+    expand(cx, sp, tts, MultiMapKind)
+}
+
+fn expand_phf_sorted_map(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                         -> Box<MacResult> {
+    expand(cx, sp, tts, SortedMapKind)
+}
+
+fn expand_phf_sorted_set(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                         -> Box<MacResult> {
+    expand(cx, sp, tts, SortedSetKind)
+}
+
+fn expand_phf_search_map(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                         -> Box<MacResult> {
+    expand(cx, sp, tts, SearchMapKind)
+}
+
+fn expand_phf_search_set(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                         -> Box<MacResult> {
+    expand(cx, sp, tts, SearchSetKind)
+}
+
+fn expand(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree], kind: Kind)
+          -> Box<MacResult> {
+    if is_item_form(tts) {
+        return match parse_items(cx, tts, kind) {
+            Some(items) => box MacItems { items: items } as Box<MacResult>,
+            None => DummyResult::any(sp),
+        };
+    }
+
+    match create_expr(cx, sp, tts, kind) {
+        Some((expr, _)) => MacExpr::new(expr),
+        None => DummyResult::expr(sp),
+    }
+}
+
+fn create_expr(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree], kind: Kind)
+               -> Option<(@Expr, MapInfo)> {
+    let map = if kind.has_values() {
+        parse_map(cx, tts)
+    } else {
+        parse_set(cx, tts)
+    };
+    let map = match map {
+        Some(map) => map,
+        None => return None,
+    };
+
+    // Repeated keys are how a multimap is written, so they are grouped rather
+    // than checked
+    if kind == MultiMapKind {
+        match map.options.on_duplicate_span {
+            Some(span) => {
+                cx.span_err(span, "`on_duplicate` does not apply to \
+                                   `phf_multimap!`, which keeps every value \
+                                   of a repeated key");
+                return None;
+            }
+            None => {}
+        }
+        return create_multimap(cx, sp, map);
+    }
+
+    match (kind, map.options.longest_prefix) {
+        (SearchMapKind, Some(span)) | (SearchSetKind, Some(span)) => {
+            cx.span_err(span, "`longest_prefix` does not apply to search maps");
+            return None;
+        }
+        _ => {}
+    }
+
+    let (map, dropped) = match apply_duplicate_policy(cx, map) {
+        Some(result) => result,
+        None => return None,
+    };
+
+    let map = match kind {
+        SortedMapKind | SortedSetKind => sort_entries(map),
+        _ => map,
+    };
+
+    let state = match generate_table_hash(cx, sp, &map) {
+        Some(state) => state,
+        None => return None,
+    };
+
+    // The ordered maps keep their entries in order, the others in hash order
+    let indices = match kind {
+        OrderedMapKind | OrderedSetKind | SortedMapKind | SortedSetKind => {
+            map.entries.iter().enumerate().map(|(i, entry)| {
+                (entry.key_str.get().to_string(), i)
+            }).collect()
+        }
+        _ => slot_indices(map.entries.as_slice(), &state),
+    };
+
+    let info = MapInfo {
+        kind: kind,
+        values: map.entries.iter().map(|entry| {
+            (entry.key_str.get().to_string(), entry.value)
+        }).collect(),
+        indices: indices,
+    };
+
+    let expr = match kind {
+        MapKind => create_map(cx, sp, map, state),
+        SetKind => create_set(cx, sp, map, state),
+        OrderedMapKind => create_ordered_map(cx, sp, map, state),
+        OrderedSetKind => create_ordered_set(cx, sp, map, state),
+        BiMapKind => {
+            match create_bimap(cx, sp, map, state) {
+                Some(expr) => expr,
+                None => return None,
+            }
+        }
+        SortedMapKind => create_sorted_map(cx, sp, map, state),
+        SortedSetKind => create_sorted_set(cx, sp, map, state),
+        SearchMapKind => {
+            match create_search_map(cx, sp, map, state) {
+                Some(expr) => expr,
+                None => return None,
+            }
+        }
+        SearchSetKind => {
+            match create_search_map(cx, sp, map, state) {
+                Some(map) => quote_expr!(cx, ::phf::PhfSearchSet { map: $map }),
+                None => return None,
+            }
+        }
+        MultiMapKind => unreachable!(),
+    };
+
+    if dropped.is_empty() {
+        return Some((expr, info));
+    }
+
+    // The values dropped by `on_duplicate` are not stored, but are still
+    // checked against the type of the value kept for their key
+    let checks = dropped.iter().map(|&(kept, dropped)| {
+        quote_stmt!(&*cx, let _ = [$kept, $dropped];)
+    }).collect();
+    let checks = cx.expr_block(cx.block(sp, checks, None));
+    let expr = quote_expr!(cx, {
+        #[allow(dead_code)]
+        fn phf_dropped_values() {
+            $checks
+        }
+        $expr
+    });
+
+    Some((expr, info))
+}
+
+// Item form invocations start with `pub` or `static`, possibly after some
+// attributes and doc comments.
+fn is_item_form(tts: &[TokenTree]) -> bool {
+    let mut tts = tts.iter();
+    loop {
+        match tts.next() {
+            Some(&TTTok(_, POUND)) => {
+                // Skip the `!` of an inner attribute and the attribute body
+                match tts.next() {
+                    Some(&TTTok(_, NOT)) => { tts.next(); }
+                    _ => {}
+                }
+            }
+            Some(&TTTok(_, DOC_COMMENT(_))) => {}
+            Some(&TTTok(_, ref tok)) => {
+                return token::is_keyword(keywords::Pub, tok) ||
+                       token::is_keyword(keywords::Static, tok);
+            }
+            _ => return false,
+        }
+    }
+}
+
+fn parse_items(cx: &mut ExtCtxt, tts: &[TokenTree], kind: Kind)
+               -> Option<Vec<@Item>> {
+    let mut parser = parse::new_parser_from_tts(cx.parse_sess(), cx.cfg(),
+                                                Vec::from_slice(tts));
+    let mut items = vec![];
+
+    while parser.token != EOF {
+        let lo = parser.span.lo;
+        let attrs = parser.parse_outer_attributes();
+        let vis = parser.parse_visibility();
+        parser.expect_keyword(keywords::Static);
+        let ident = parser.parse_ident();
+
+        let ty = match kind {
+            MapKind => {
+                parser.expect(&COLON);
+                let ty = parser.parse_ty(false);
+                quote_ty!(cx, ::phf::PhfMap<$ty>)
+            }
+            OrderedMapKind => {
+                parser.expect(&COLON);
+                let ty = parser.parse_ty(false);
+                quote_ty!(cx, ::phf::PhfOrderedMap<$ty>)
+            }
+            SetKind => quote_ty!(cx, ::phf::PhfSet),
+            OrderedSetKind => quote_ty!(cx, ::phf::PhfOrderedSet),
+            BiMapKind => {
+                parser.expect(&COLON);
+                let ty = parser.parse_ty(false);
+                quote_ty!(cx, ::phf::PhfBiMap<$ty>)
+            }
+            MultiMapKind => {
+                parser.expect(&COLON);
+                let ty = parser.parse_ty(false);
+                quote_ty!(cx, ::phf::PhfMultiMap<$ty>)
+            }
+            SortedMapKind => {
+                parser.expect(&COLON);
+                let ty = parser.parse_ty(false);
+                quote_ty!(cx, ::phf::PhfSortedMap<$ty>)
+            }
+            SortedSetKind => quote_ty!(cx, ::phf::PhfSortedSet),
+            SearchMapKind => {
+                parser.expect(&COLON);
+                let ty = parser.parse_ty(false);
+                quote_ty!(cx, ::phf::PhfSearchMap<$ty>)
+            }
+            SearchSetKind => quote_ty!(cx, ::phf::PhfSearchSet),
+        };
+
+        parser.expect(&EQ);
+        parser.expect(&LBRACE);
+        let body = parser.parse_seq_to_end(&RBRACE, seq_sep_none(),
+                                           |p| p.parse_token_tree());
+        parser.expect(&SEMI);
+        let sp = mk_sp(lo, parser.last_span.hi);
+
+        let (expr, info) = match create_expr(cx, sp, body.as_slice(), kind) {
+            Some(created) => created,
+            None => return None,
+        };
+
+        let name = item_path(cx, ident);
+        with_registry(|registry| {
+            let info = if registry.maps.contains_key(&name) {
+                None
+            } else {
+                Some(info.clone())
+            };
+            registry.maps.insert(name.clone(), info);
+        });
+
+        let item = cx.item_static(sp, ident, ty, ast::MutImmutable, expr);
+        items.push(@Item {
+            attrs: attrs,
+            vis: vis,
+            ..(*item).clone()
+        });
+    }
+
+    Some(items)
+}
+
+// A map declared with the item form, as named in the arguments of a macro
+struct MapRef {
+    // The map, named by its path from the crate root so that nothing declared
+    // closer to the macro can shadow it
+    map: @Expr,
+    name: String,
+    // The path of the map from the crate root, as used by the registry
+    path: String,
+    info: MapInfo,
+}
+
+fn parse_map_ref(cx: &mut ExtCtxt, parser: &mut Parser) -> Option<MapRef> {
+    let map = parser.parse_expr();
+    let (name, path) = match map.node {
+        ExprPath(ref path) => {
+            let segments = path.segments.iter().map(|segment| {
+                segment.identifier
+            }).collect::<Vec<ast::Ident>>();
+            (join_path(segments.as_slice()), resolve_path(cx, path))
+        }
+        _ => {
+            cx.span_err(map.span, "expected the path of a map");
+            return None;
+        }
+    };
+
+    let info = match path {
+        Some(ref path) => with_registry(|registry| {
+            registry.maps.find(&join_path(path.as_slice()))
+                         .map(|info| info.clone())
+        }),
+        None => None,
+    };
+    let info = match info {
+        Some(Some(info)) => info,
+        Some(None) => {
+            cx.span_err(map.span,
+                        format!("`{}` refers to more than one map", name)
+                               .as_slice());
+            return None;
+        }
+        None => {
+            cx.span_err(map.span,
+                        format!("`{}` is not a map declared earlier in this \
+                                 crate with the item form of one of the map \
+                                 macros", name).as_slice());
+            return None;
+        }
     };
 
-    if has_duplicates(cx, sp, entries.as_slice()) {
+    // The first element of the path is the crate
+    let path = path.unwrap();
+    let segments = Vec::from_slice(path.as_slice().tail());
+    let global = cx.expr_path(cx.path_global(map.span, segments));
+
+    Some(MapRef {
+        map: global,
+        name: name,
+        path: join_path(path.as_slice()),
+        info: info,
+    })
+}
+
+// The arguments of a compile time lookup: the path of a map declared with the
+// item form and a key
+struct Lookup {
+    map: MapRef,
+    key_str: InternedString,
+    key: @Expr,
+}
+
+fn parse_lookup(cx: &mut ExtCtxt, sp: Span, parser: &mut Parser)
+                -> Option<Lookup> {
+    let map = match parse_map_ref(cx, parser) {
+        Some(map) => map,
+        None => return None,
+    };
+
+    if !parser.eat(&COMMA) {
+        cx.span_err(parser.span, "expected `,`");
+        return None;
+    }
+
+    let mut keys = vec![];
+    if !parse_keys(cx, parser.parse_expr(), &mut keys) {
+        return None;
+    }
+    if keys.len() != 1 {
+        cx.span_err(sp, "expected a single key");
+        return None;
+    }
+    let (key_str, key) = keys.pop().unwrap();
+
+    if parser.token != EOF {
+        cx.span_err(parser.span, "expected end of arguments");
+        return None;
+    }
+
+    Some(Lookup {
+        map: map,
+        key_str: key_str,
+        key: key,
+    })
+}
+
+fn expand_phf_get(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                  -> Box<MacResult> {
+    let mut parser = parse::new_parser_from_tts(cx.parse_sess(), cx.cfg(),
+                                                Vec::from_slice(tts));
+    let Lookup { map: MapRef { map, name, info, .. }, key_str, key } =
+        match parse_lookup(cx, sp, &mut parser) {
+            Some(lookup) => lookup,
+            None => return DummyResult::expr(sp),
+        };
+
+    if !info.kind.has_values() || info.kind == MultiMapKind {
+        cx.span_err(map.span,
+                    format!("`{}` is not a map with a single value for each \
+                             key", name).as_slice());
+        return DummyResult::expr(sp);
+    }
+
+    match info.values.find(&key_str.get().to_string()) {
+        Some(&idx) if info.kind == BiMapKind ||
+                      info.kind == SortedMapKind ||
+                      info.kind == SearchMapKind => {
+            MacExpr::new(quote_expr!(cx, &$map.map.values[$idx]))
+        }
+        Some(&idx) => MacExpr::new(quote_expr!(cx, &$map.values[$idx])),
+        None => {
+            cx.span_err(key.span,
+                        format!("map `{}` has no key `{}`", name, key_str)
+                               .as_slice());
+            DummyResult::expr(sp)
+        }
+    }
+}
+
+// Parses the path of an atom type followed by a comma, returning the path,
+// its name as written and the path from the crate root it resolves to
+fn parse_atom_type(cx: &mut ExtCtxt, parser: &mut Parser)
+                   -> Option<(ast::Path, String, Option<String>)> {
+    let ty = parser.parse_expr();
+    let path = match ty.node {
+        ExprPath(ref path) => path.clone(),
+        _ => {
+            cx.span_err(ty.span, "expected the path of a type");
+            return None;
+        }
+    };
+
+    if !parser.eat(&COMMA) {
+        cx.span_err(parser.span, "expected `,`");
+        return None;
+    }
+
+    let segments = path.segments.iter().map(|segment| {
+        segment.identifier
+    }).collect::<Vec<ast::Ident>>();
+    let name = join_path(segments.as_slice());
+    let resolved = resolve_path(cx, &path).map(|path| {
+        join_path(path.as_slice())
+    });
+    Some((path, name, resolved))
+}
+
+fn expand_phf_atom_set(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                       -> Box<MacResult> {
+    let mut parser = parse::new_parser_from_tts(cx.parse_sess(), cx.cfg(),
+                                                Vec::from_slice(tts));
+
+    let (ty, ty_name, resolved) = match parse_atom_type(cx, &mut parser) {
+        Some(parsed) => parsed,
+        None => return DummyResult::any(sp),
+    };
+    let MapRef { map, name, path, info } =
+        match parse_map_ref(cx, &mut parser) {
+            Some(map) => map,
+            None => return DummyResult::any(sp),
+        };
+
+    if parser.token != EOF {
+        cx.span_err(parser.span, "expected end of arguments");
+        return DummyResult::any(sp);
+    }
+
+    if info.kind != SetKind {
+        cx.span_err(map.span,
+                    format!("`{}` was not declared with `phf_set!`", name)
+                           .as_slice());
+        return DummyResult::any(sp);
+    }
+
+    let resolved = match resolved {
+        Some(resolved) => resolved,
+        None => {
+            cx.span_err(ty.span,
+                        format!("`{}` is not a path through modules, `self` \
+                                 and `super`", ty_name).as_slice());
+            return DummyResult::any(sp);
+        }
+    };
+    with_registry(|registry| {
+        let set = if registry.atom_sets.contains_key(&resolved) {
+            None
+        } else {
+            Some(path.clone())
+        };
+        registry.atom_sets.insert(resolved.clone(), set);
+    });
+
+    let ty = cx.ty_path(ty, None);
+    let item = quote_item!(cx,
+        impl ::phf::atom::AtomSet for $ty {
+            fn static_atoms(_: Option<$ty>) -> &'static ::phf::PhfSet {
+                &$map
+            }
+        }
+    ).unwrap();
+
+    box MacItems { items: vec![item] } as Box<MacResult>
+}
+
+fn expand_phf_atom(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                   -> Box<MacResult> {
+    let mut parser = parse::new_parser_from_tts(cx.parse_sess(), cx.cfg(),
+                                                Vec::from_slice(tts));
+
+    let (ty, ty_name, resolved) = match parse_atom_type(cx, &mut parser) {
+        Some(parsed) => parsed,
+        None => return DummyResult::expr(sp),
+    };
+    let Lookup { map: MapRef { map, name, path, info }, key_str, key } =
+        match parse_lookup(cx, sp, &mut parser) {
+            Some(lookup) => lookup,
+            None => return DummyResult::expr(sp),
+        };
+
+    if info.kind != SetKind {
+        cx.span_err(map.span,
+                    format!("`{}` was not declared with `phf_set!`", name)
+                           .as_slice());
+        return DummyResult::expr(sp);
+    }
+
+    // The set must be the one `phf_atom_set!` declared for the type, so that
+    // the index refers to the right string
+    let set = match resolved {
+        Some(ref resolved) => with_registry(|registry| {
+            registry.atom_sets.find(resolved).map(|set| set.clone())
+        }),
+        None => None,
+    };
+    match set {
+        Some(Some(ref set)) if *set == path => {}
+        Some(Some(set)) => {
+            cx.span_err(map.span,
+                        format!("the static atoms of `{}` are the values of \
+                                 `{}`, not of `{}`", ty_name, set, name)
+                               .as_slice());
+            return DummyResult::expr(sp);
+        }
+        Some(None) => {
+            cx.span_err(ty.span,
+                        format!("`{}` refers to more than one atom type",
+                                ty_name).as_slice());
+            return DummyResult::expr(sp);
+        }
+        None => {
+            cx.span_err(ty.span,
+                        format!("`{}` is not a type whose `AtomSet` \
+                                 implementation was declared earlier in this \
+                                 crate with `phf_atom_set!`", ty_name)
+                               .as_slice());
+            return DummyResult::expr(sp);
+        }
+    }
+
+    let idx = match info.indices.find(&key_str.get().to_string()) {
+        Some(&idx) => idx,
+        None => {
+            cx.span_err(key.span,
+                        format!("set `{}` has no value `{}`", name, key_str)
+                               .as_slice());
+            return DummyResult::expr(sp);
+        }
+    };
+
+    // The encoding of a static atom, as in `Atom::from_slice`
+    let data = idx << 1 | 1;
+    let ty = cx.ty_path(ty, None);
+    MacExpr::new(quote_expr!(cx, ::phf::atom::Atom::<$ty> { data: $data }))
+}
+
+fn expand_phf_match(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                    -> Box<MacResult> {
+    let mut parser = parse::new_parser_from_tts(cx.parse_sess(), cx.cfg(),
+                                                Vec::from_slice(tts));
+
+    let key = parser.parse_expr();
+    if !parser.eat(&COMMA) {
+        cx.span_err(parser.span, "expected `,`");
+        return DummyResult::expr(sp);
+    }
+
+    let mut entries = vec![];
+    let mut bodies = vec![];
+    let mut fallback = None;
+
+    let mut bad = false;
+    while parser.token != EOF {
+        if fallback.is_some() {
+            cx.span_err(parser.span, "unreachable arm after the `_` arm");
+            return DummyResult::expr(sp);
+        }
+
+        let attrs = parser.parse_outer_attributes();
+
+        let mut keys = vec![];
+        let wild = parser.eat(&UNDERSCORE);
+        if !wild && !parse_keys(cx, parser.parse_expr(), &mut keys) {
+            bad = true;
+        }
+
+        if !parser.eat(&FAT_ARROW) {
+            cx.span_err(parser.span, "expected `=>`");
+            return DummyResult::expr(sp);
+        }
+
+        let body = parser.parse_expr();
+        let require_comma = !classify::expr_is_simple_block(body);
+
+        if is_configured(cx, attrs.as_slice()) {
+            if wild {
+                fallback = Some(body);
+            } else {
+                for (key_str, key) in keys.move_iter() {
+                    entries.push(Entry {
+                        key_str: key_str,
+                        key: key,
+                        value: bodies.len(),
+                    });
+                }
+                bodies.push(body);
+            }
+        }
+
+        if !parser.eat(&COMMA) && require_comma && parser.token != EOF {
+            cx.span_err(parser.span, "expected `,`");
+            return DummyResult::expr(sp);
+        }
+    }
+
+    let fallback = match fallback {
+        Some(fallback) => fallback,
+        None => {
+            cx.span_err(sp, "`phf_match!` requires a `_` arm");
+            return DummyResult::expr(sp);
+        }
+    };
+
+    if entries.len() > phf::MAX_SIZE {
+        cx.span_err(sp,
+                    format!("maps with more than {} entries are not supported",
+                            phf::MAX_SIZE).as_slice());
+        return DummyResult::expr(sp);
+    }
+
+    if bad || has_duplicates(cx, entries.as_slice()) {
         return DummyResult::expr(sp);
     }
 
+    // The map takes each key to the index of its arm
+    let map = ParsedMap {
+        entries: entries,
+        values: range(0, bodies.len()).map(|i| quote_expr!(&*cx, $i)).collect(),
+        options: Options::new(),
+    };
+    let state = generate_hash(cx, sp, map.entries.as_slice());
+    let map = create_map(cx, sp, map, state);
+
+    let mut arms = bodies.move_iter().enumerate().map(|(i, body)| {
+        cx.arm(body.span, vec![quote_pat!(&*cx, Some(&$i))], body)
+    }).collect::<Vec<_>>();
+    arms.push(cx.arm(fallback.span, vec![cx.pat_wild(fallback.span)],
+                     fallback));
+
+    let lookup = quote_expr!(cx, PHF_MATCH_ARMS.find(&$key));
+    let match_expr = cx.expr_match(sp, lookup, arms);
+
+    MacExpr::new(quote_expr!(cx, {
+        static PHF_MATCH_ARMS: ::phf::PhfMap<uint> = $map;
+        $match_expr
+    }))
+}
+
+fn expand_phf_enum(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                   -> Box<MacResult> {
+    let mut parser = parse::new_parser_from_tts(cx.parse_sess(), cx.cfg(),
+                                                Vec::from_slice(tts));
+
+    let attrs = parser.parse_outer_attributes();
+    let vis = parser.parse_visibility();
+    parser.expect_keyword(keywords::Enum);
+    let name = parser.parse_ident();
+    parser.expect(&LBRACE);
+
+    let mut variants = vec![];
+    let mut entries = vec![];
+    let mut values = vec![];
+
+    let mut bad = false;
+    while parser.token != RBRACE {
+        let lo = parser.span.lo;
+        let variant_attrs = parser.parse_outer_attributes();
+        let ident = parser.parse_ident();
+        let variant_sp = mk_sp(lo, parser.last_span.hi);
+        parser.expect(&EQ);
+
+        let spelling = parser.parse_expr();
+        let mut keys = vec![];
+        if !parse_keys(cx, spelling, &mut keys) {
+            bad = true;
+        } else if keys.len() != 1 {
+            cx.span_err(spelling.span, "a variant must have exactly one \
+                                        spelling");
+            bad = true;
+        }
+
+        let configured = attr::test_cfg(cx.cfg().as_slice(),
+                                        variant_attrs.iter().map(|a| *a));
+        if configured {
+            for (key_str, key) in keys.move_iter() {
+                entries.push(Entry {
+                    key_str: key_str,
+                    key: key,
+                    value: values.len(),
+                });
+            }
+            values.push(cx.expr_ident(variant_sp, ident));
+
+            let mut variant = cx.variant(variant_sp, ident, vec![]);
+            variant.node.attrs = variant_attrs;
+            variants.push(@variant);
+        }
+
+        if !parser.eat(&COMMA) && parser.token != RBRACE {
+            cx.span_err(parser.span, "expected `,`");
+            return DummyResult::any(sp);
+        }
+    }
+    parser.expect(&RBRACE);
+
+    if parser.token != EOF {
+        cx.span_err(parser.span, "unexpected token after the enum");
+        return DummyResult::any(sp);
+    }
+
+    if bad || has_duplicates(cx, entries.as_slice()) {
+        return DummyResult::any(sp);
+    }
+
     let state = generate_hash(cx, sp, entries.as_slice());
 
-    create_map(cx, sp, entries, state)
+    // The position of each variant's spelling in the map's entries
+    let mut slots = Vec::from_elem(values.len(), 0u);
+    for (slot, &idx) in state.map.iter().enumerate() {
+        *slots.get_mut(entries.get(idx).value) = slot;
+    }
+
+    let arms = variants.iter().zip(slots.iter()).map(|(variant, &slot)| {
+        let pat = cx.pat_ident(variant.span, variant.node.name);
+        cx.arm(variant.span, vec![pat], cx.expr_uint(variant.span, slot))
+    }).collect();
+    let slot = cx.expr_match(sp, quote_expr!(cx, *self), arms);
+
+    let map = ParsedMap {
+        entries: entries,
+        values: values,
+        options: Options::new(),
+    };
+    let map = create_map(cx, sp, map, state);
+
+    let item = cx.item_enum(sp, name, ast::EnumDef { variants: variants });
+    let item = @Item {
+        attrs: attrs,
+        vis: vis,
+        ..(*item).clone()
+    };
+
+    let methods = quote_item!(cx,
+        impl $name {
+            /// Returns the map from each spelling to its variant.
+            pub fn map() -> &'static ::phf::PhfMap<$name> {
+                static MAP: ::phf::PhfMap<$name> = $map;
+                &MAP
+            }
+
+            /// Returns the spelling of this variant.
+            ///
+            /// This is the key stored in the map, so it is the same string
+            /// that `find_key` returns.
+            pub fn as_str(&self) -> &'static str {
+                let (key, _) = $name::map().entries[$slot];
+                key
+            }
+        }
+    ).unwrap();
+
+    let from_str = quote_item!(cx,
+        impl ::std::from_str::FromStr for $name {
+            fn from_str(s: &str) -> Option<$name> {
+                $name::map().find(&s).map(|&variant| variant)
+            }
+        }
+    ).unwrap();
+
+    box MacItems { items: vec![item, methods, from_str] } as Box<MacResult>
 }
 
-fn expand_phf_set(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
-                  -> Box<MacResult> {
-    let entries = match parse_set(cx, tts) {
-        Some(entries) => entries,
-        None => return DummyResult::expr(sp)
+fn expand_phf_table(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
+                    -> Box<MacResult> {
+    let mut parser = parse::new_parser_from_tts(cx.parse_sess(), cx.cfg(),
+                                                Vec::from_slice(tts));
+    let mut items = vec![];
+
+    while parser.token != EOF {
+        let lo = parser.span.lo;
+        let attrs = parser.parse_outer_attributes();
+        let vis = parser.parse_visibility();
+        parser.expect_keyword(keywords::Static);
+        let ident = parser.parse_ident();
+        parser.expect(&COLON);
+        let name = parser.parse_ident();
+
+        parser.expect(&LBRACE);
+        let mut columns = vec![];
+        while parser.token != RBRACE {
+            let column_sp = parser.span;
+            let column = parser.parse_ident();
+            parser.expect(&COLON);
+            let ty = parser.parse_ty(false);
+            columns.push(Column { ident: column, ty: ty, span: column_sp });
+
+            if !parser.eat(&COMMA) && parser.token != RBRACE {
+                cx.span_err(parser.span, "expected `,`");
+                return DummyResult::any(sp);
+            }
+        }
+        parser.expect(&RBRACE);
+
+        parser.expect(&EQ);
+        parser.expect(&LBRACE);
+        let body = parser.parse_seq_to_end(&RBRACE, seq_sep_none(),
+                                           |p| p.parse_token_tree());
+        parser.expect(&SEMI);
+        let sp = mk_sp(lo, parser.last_span.hi);
+
+        let table = Table {
+            attrs: attrs,
+            vis: vis,
+            ident: ident,
+            name: name,
+            columns: columns,
+        };
+        match create_table(cx, sp, table, body.as_slice()) {
+            Some(table_items) => items.push_all_move(table_items),
+            None => return DummyResult::any(sp),
+        }
+    }
+
+    box MacItems { items: items } as Box<MacResult>
+}
+
+struct Column {
+    ident: ast::Ident,
+    ty: @ast::Ty,
+    span: Span,
+}
+
+// The declaration of a `phf_table!` static and of its type
+struct Table {
+    attrs: Vec<Attribute>,
+    vis: ast::Visibility,
+    ident: ast::Ident,
+    name: ast::Ident,
+    columns: Vec<Column>,
+}
+
+fn create_table(cx: &mut ExtCtxt, sp: Span, table: Table, tts: &[TokenTree])
+                -> Option<Vec<@Item>> {
+    let mut bad = false;
+    for column in table.columns.iter() {
+        let column_name = token::get_ident(column.ident);
+        if column_name.get() == "keys" || column_name.get() == "index_of" {
+            cx.span_err(column.span,
+                        format!("a column may not be named `{}`", column_name)
+                               .as_slice());
+            bad = true;
+        }
+    }
+    // Each column also gets a `find_` method, which may not clash with
+    // another column
+    for column in table.columns.iter() {
+        let column_name = token::get_ident(column.ident);
+        let clashes = table.columns.iter().any(|other| {
+            let other_name = token::get_ident(other.ident);
+            column_name.get() == format!("find_{}", other_name).as_slice()
+        });
+        if clashes {
+            cx.span_err(column.span,
+                        format!("the column `{}` clashes with the lookup \
+                                 method of another column", column_name)
+                               .as_slice());
+            bad = true;
+        }
+    }
+    if table.columns.is_empty() {
+        cx.span_err(sp, "a table must have at least one column");
+        bad = true;
+    }
+
+    let map = match parse_map(cx, tts) {
+        Some(map) => map,
+        None => return None,
+    };
+    let (map, dropped) = match apply_duplicate_policy(cx, map) {
+        Some(result) => result,
+        None => return None,
+    };
+
+    // Each value is a tuple with an element for each column, or the element
+    // itself if there is only one column. Rows dropped by `on_duplicate` are
+    // not stored in any column.
+    let mut rows = vec![];
+    let mut dropped_rows = vec![];
+    let values = map.values.iter().map(|&value| (value, false));
+    let dropped_values = dropped.iter().map(|&(_, value)| (value, true));
+    for (value, is_dropped) in values.chain(dropped_values) {
+        let cells = match value.node {
+            ast::ExprTup(ref cells) if table.columns.len() > 1 ||
+                                       cells.len() == 1 => cells.clone(),
+            _ if table.columns.len() == 1 => vec![value],
+            _ => vec![],
+        };
+        if cells.len() != table.columns.len() {
+            cx.span_err(value.span,
+                        format!("expected a tuple of {} values",
+                                table.columns.len()).as_slice());
+            bad = true;
+        }
+        if is_dropped {
+            dropped_rows.push(cells);
+        } else {
+            rows.push(cells);
+        }
+    }
+
+    if bad {
+        return None;
+    }
+
+    let state = match generate_table_hash(cx, sp, &map) {
+        Some(state) => state,
+        None => return None,
     };
 
-    if has_duplicates(cx, sp, entries.as_slice()) {
-        return DummyResult::expr(sp);
-    }
+    // The cells of each column are stored in the order of the slots of the
+    // keys, so that the result of `index_of` indexes every column
+    let name = table.name;
+    let mut fields = vec![];
+    let mut values = vec![];
+    let mut methods = vec![];
+    let mut checks = vec![];
+    for (i, column) in table.columns.iter().enumerate() {
+        let cells = state.map.iter().map(|&idx| {
+            *rows.get(map.entries.get(idx).value).get(i)
+        }).collect();
+        let cells = create_slice_expr(cells, sp);
+
+        let ident = column.ident;
+        let find_ident = cx.ident_of(format!("find_{}",
+                                             token::get_ident(ident))
+                                            .as_slice());
+        let ty = column.ty;
+
+        let dropped_cells = dropped_rows.iter().map(|row| *row.get(i))
+                                        .collect::<Vec<@Expr>>();
+        if !dropped_cells.is_empty() {
+            let dropped_cells = create_slice_expr(dropped_cells, sp);
+            checks.push(quote_stmt!(&*cx,
+                let _: &'static [$ty] = &$dropped_cells;
+            ));
+        }
 
-    let state = generate_hash(cx, sp, entries.as_slice());
+        fields.push(create_struct_field(column.span, ident,
+                                        quote_ty!(cx, &'static [$ty])));
+        values.push(cx.field_imm(column.span, ident,
+                                 quote_expr!(cx, &'static $cells)));
+        methods.push(quote_item!(cx,
+            impl $name {
+                /// Returns a column of the table, indexed by `index_of`.
+                #[inline]
+                pub fn $ident(&self) -> &'static [$ty] {
+                    self.$ident
+                }
 
-    create_set(cx, sp, entries, state)
-}
+                /// Returns the cell of `key` in a column of the table.
+                #[inline]
+                pub fn $find_ident(&self, key: & &str)
+                                   -> Option<&'static $ty> {
+                    self.index_of(key).map(|idx| &self.$ident[idx])
+                }
+            }
+        ).unwrap());
+    }
 
-fn expand_phf_ordered_map(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
-                          -> Box<MacResult> {
-    let entries = match parse_map(cx, tts) {
-        Some(entries) => entries,
-        None => return DummyResult::expr(sp),
+    // The keys are stored as a set, all sharing one `()` value
+    let keys = ParsedMap {
+        entries: map.entries.move_iter().map(|entry| {
+            Entry { value: 0, ..entry }
+        }).collect(),
+        values: vec![quote_expr!(&*cx, ())],
+        options: map.options,
     };
+    let keys = create_set(cx, sp, keys, state);
+    let keys_ident = cx.ident_of("keys");
+    fields.unshift(create_struct_field(sp, keys_ident,
+                                       quote_ty!(cx, ::phf::PhfSet)));
+    values.unshift(cx.field_imm(sp, keys_ident, keys));
+
+    let item = cx.item_struct(sp, name, ast::StructDef {
+        fields: fields,
+        ctor_id: None,
+        super_struct: None,
+        is_virtual: false,
+    });
+    let mut items = vec![@Item {
+        attrs: table.attrs.clone(),
+        vis: table.vis,
+        ..(*item).clone()
+    }];
+
+    items.push(quote_item!(cx,
+        impl $name {
+            /// Returns the set of the table's keys.
+            #[inline]
+            pub fn keys(&self) -> &::phf::PhfSet {
+                &self.keys
+            }
 
-    if has_duplicates(cx, sp, entries.as_slice()) {
-        return DummyResult::expr(sp);
+            /// Returns the index of the cells of `key` in each column.
+            #[inline]
+            pub fn index_of(&self, key: & &str) -> Option<uint> {
+                self.keys.index_of(key)
+            }
+
+            /// Returns the index of the cells of a prehashed key in each
+            /// column.
+            #[inline]
+            pub fn index_of_prehashed(&self, key: &::phf::PhfKey)
+                                      -> Option<uint> {
+                self.keys.index_of_prehashed(key)
+            }
+        }
+    ).unwrap());
+    items.push_all_move(methods);
+
+    // The cells of the dropped rows are never stored, but are still checked
+    // against the types of their columns
+    if !checks.is_empty() {
+        let checks = cx.expr_block(cx.block(sp, checks, None));
+        items.push(quote_item!(cx,
+            impl $name {
+                #[allow(dead_code)]
+                fn phf_dropped_rows() {
+                    $checks
+                }
+            }
+        ).unwrap());
     }
 
-    let state = generate_hash(cx, sp, entries.as_slice());
+    let expr = cx.expr_struct_ident(sp, name, values);
+    let ty = cx.ty_ident(sp, name);
+    let item = cx.item_static(sp, table.ident, ty, ast::MutImmutable, expr);
+    items.push(@Item {
+        attrs: table.attrs,
+        vis: table.vis,
+        ..(*item).clone()
+    });
 
-    create_ordered_map(cx, sp, entries, state)
+    Some(items)
 }
 
-fn expand_phf_ordered_set(cx: &mut ExtCtxt, sp: Span, tts: &[TokenTree])
-                          -> Box<MacResult> {
-    let entries = match parse_set(cx, tts) {
-        Some(entries) => entries,
-        None => return DummyResult::expr(sp)
-    };
-
-    if has_duplicates(cx, sp, entries.as_slice()) {
-        return DummyResult::expr(sp);
+fn create_struct_field(sp: Span, ident: ast::Ident, ty: @ast::Ty)
+                       -> ast::StructField {
+    Spanned {
+        node: ast::StructField_ {
+            kind: ast::NamedField(ident, ast::Inherited),
+            id: ast::DUMMY_NODE_ID,
+            ty: ty,
+            attrs: vec![],
+        },
+        span: sp,
     }
-
-    let state = generate_hash(cx, sp, entries.as_slice());
-
-    create_ordered_set(cx, sp, entries, state)
 }
 
-fn parse_map(cx: &mut ExtCtxt, tts: &[TokenTree]) -> Option<Vec<Entry>> {
+fn parse_map(cx: &mut ExtCtxt, tts: &[TokenTree]) -> Option<ParsedMap> {
     let mut parser = parse::new_parser_from_tts(cx.parse_sess(), cx.cfg(),
                                                 Vec::from_slice(tts));
+    let options = match parse_options(cx, &mut parser) {
+        Some(options) => options,
+        None => return None,
+    };
     let mut entries = Vec::new();
+    let mut values = Vec::new();
 
     let mut bad = false;
     while parser.token != EOF {
-        let key = cx.expand_expr(parser.parse_expr());
-        let key_str = parse_str(cx, key).unwrap_or_else(|| {
+        let attrs = parser.parse_outer_attributes();
+
+        let mut keys = vec![];
+        if !parse_keys(cx, parser.parse_expr(), &mut keys) {
             bad = true;
-            InternedString::new("")
-        });
+        }
 
         if !parser.eat(&FAT_ARROW) {
             cx.span_err(parser.span, "expected `=>`");
             return None;
         }
 
-        let value = parser.parse_expr();
+        let value = reference_table(cx, parser.parse_expr());
 
-        entries.push(Entry {
-            key_str: key_str,
-            key: key,
-            value: value
-        });
+        if is_configured(cx, attrs.as_slice()) {
+            for (key_str, key) in keys.move_iter() {
+                entries.push(Entry {
+                    key_str: key_str,
+                    key: key,
+                    value: values.len(),
+                });
+            }
+            values.push(value);
+        }
 
         if !parser.eat(&COMMA) && parser.token != EOF {
             cx.span_err(parser.span, "expected `,`");
@@ -169,28 +1511,72 @@ fn parse_map(cx: &mut ExtCtxt, tts: &[TokenTree]) -> Option<Vec<Entry>> {
         return None;
     }
 
-    Some(entries)
+    Some(ParsedMap {
+        entries: entries,
+        values: values,
+        options: options,
+    })
+}
+
+// A value which is itself a table invocation is stored by reference, so that
+// maps of maps have the type `PhfMap<&'static PhfMap<T>>`.
+fn reference_table(cx: &mut ExtCtxt, value: @Expr) -> @Expr {
+    static TABLE_MACROS: &'static [&'static str] = &[
+        "phf_map",
+        "phf_set",
+        "phf_ordered_map",
+        "phf_ordered_set",
+        "phf_bimap",
+        "phf_multimap",
+        "phf_sorted_map",
+        "phf_sorted_set",
+        "phf_search_map",
+        "phf_search_set",
+    ];
+
+    let path = match value.node {
+        ExprMac(Spanned { node: MacInvocTT(ref path, _, _), .. }) => path,
+        _ => return value,
+    };
+    if path.segments.len() != 1 {
+        return value;
+    }
+
+    let name = token::get_ident(path.segments.get(0).identifier);
+    if TABLE_MACROS.contains(&name.get()) {
+        cx.expr_addr_of(value.span, value)
+    } else {
+        value
+    }
 }
 
-fn parse_set(cx: &mut ExtCtxt, tts: &[TokenTree]) -> Option<Vec<Entry>> {
+fn parse_set(cx: &mut ExtCtxt, tts: &[TokenTree]) -> Option<ParsedMap> {
     let mut parser = parse::new_parser_from_tts(cx.parse_sess(), cx.cfg(),
                                                 Vec::from_slice(tts));
+    let options = match parse_options(cx, &mut parser) {
+        Some(options) => options,
+        None => return None,
+    };
     let mut entries = Vec::new();
-    let value = quote_expr!(&*cx, ());
 
     let mut bad = false;
     while parser.token != EOF {
-        let key = cx.expand_expr(parser.parse_expr());
-        let key_str = parse_str(cx, key).unwrap_or_else(|| {
+        let attrs = parser.parse_outer_attributes();
+
+        let mut keys = vec![];
+        if !parse_keys(cx, parser.parse_expr(), &mut keys) {
             bad = true;
-            InternedString::new("")
-        });
+        }
 
-        entries.push(Entry {
-            key_str: key_str,
-            key: key,
-            value: value,
-        });
+        if is_configured(cx, attrs.as_slice()) {
+            for (key_str, key) in keys.move_iter() {
+                entries.push(Entry {
+                    key_str: key_str,
+                    key: key,
+                    value: 0,
+                });
+            }
+        }
 
         if !parser.eat(&COMMA) && parser.token != EOF {
             cx.span_err(parser.span, "expected `,`");
@@ -209,7 +1595,112 @@ fn parse_set(cx: &mut ExtCtxt, tts: &[TokenTree]) -> Option<Vec<Entry>> {
         return None;
     }
 
-    Some(entries)
+    // Every value of a set is `()`, so they can all share one
+    Some(ParsedMap {
+        entries: entries,
+        values: vec![quote_expr!(&*cx, ())],
+        options: options,
+    })
+}
+
+fn parse_options(cx: &mut ExtCtxt, parser: &mut Parser) -> Option<Options> {
+    let mut options = Options::new();
+
+    let mut bad = false;
+    while parser.token == POUND && parser.look_ahead(1, |t| *t == NOT) {
+        let attr = parser.parse_attribute(true);
+
+        if attr.check_name("on_duplicate") {
+            options.on_duplicate_span = Some(attr.span);
+            let policy = attr.value_str();
+            options.on_duplicate = match policy.as_ref().map(|s| s.get()) {
+                Some("error") => ErrorOnDuplicate,
+                Some("first") => KeepFirst,
+                Some("last") => KeepLast,
+                _ => {
+                    cx.span_err(attr.span,
+                                "expected `on_duplicate = \"error\"`, \
+                                 `\"first\"` or `\"last\"`");
+                    bad = true;
+                    continue;
+                }
+            };
+        } else if attr.check_name("seed") {
+            let seed = match attr.node.value.node {
+                ast::MetaNameValue(_, ref lit) => {
+                    match lit.node {
+                        LitUint(seed, _) => Some(seed),
+                        LitIntUnsuffixed(seed) if seed >= 0 => {
+                            Some(seed as u64)
+                        }
+                        _ => None,
+                    }
+                }
+                _ => None,
+            };
+            if seed.is_none() {
+                cx.span_err(attr.span, "expected `seed = N` with an integer N");
+                bad = true;
+            }
+            options.seed = seed;
+        } else if attr.check_name("longest_prefix") {
+            match attr.node.value.node {
+                ast::MetaWord(_) => options.longest_prefix = Some(attr.span),
+                _ => {
+                    cx.span_err(attr.span, "expected `longest_prefix`");
+                    bad = true;
+                }
+            }
+        } else {
+            cx.span_err(attr.span, "unknown option");
+            bad = true;
+        }
+    }
+
+    if bad {
+        None
+    } else {
+        Some(options)
+    }
+}
+
+// Splits `"a" | "b"` into its alternatives, resolving `#[phf_key]` statics and
+// expanding macros in each.
+fn parse_keys(cx: &mut ExtCtxt, e: @Expr,
+              keys: &mut Vec<(InternedString, @Expr)>) -> bool {
+    match e.node {
+        ExprBinary(BiBitOr, left, right) => {
+            let left_ok = parse_keys(cx, left, keys);
+            parse_keys(cx, right, keys) && left_ok
+        }
+        _ => {
+            let key = match resolve_key(cx, e) {
+                Some(key) => key,
+                None => return false,
+            };
+            let key = cx.expand_expr(key);
+            match parse_str(cx, key) {
+                Some(key_str) => {
+                    keys.push((key_str, key));
+                    true
+                }
+                None => false,
+            }
+        }
+    }
+}
+
+// Entries may only carry `#[cfg]` attributes, which are checked against the
+// crate's configuration.
+fn is_configured(cx: &mut ExtCtxt, attrs: &[Attribute]) -> bool {
+    for attr in attrs.iter() {
+        if !attr.check_name("cfg") {
+            cx.span_err(attr.span,
+                        "only `#[cfg]` attributes are allowed on entries");
+        }
+    }
+
+    attr::test_cfg(cx.cfg().as_slice(), attrs.iter().map(|attr| *attr))
 }
 
 fn parse_str(cx: &mut ExtCtxt, e: &Expr) -> Option<InternedString> {
@@ -230,159 +1721,206 @@ fn parse_str(cx: &mut ExtCtxt, e: &Expr) -> Option<InternedString> {
     }
 }
 
-fn has_duplicates(cx: &mut ExtCtxt, sp: Span, entries: &[Entry]) -> bool {
+fn has_duplicates(cx: &mut ExtCtxt, entries: &[Entry]) -> bool {
     let mut dups = false;
-    let mut strings = HashMap::new();
+    let mut originals = HashMap::new();
     for entry in entries.iter() {
-        let spans = strings.find_or_insert(entry.key_str.clone(), vec![]);
-        spans.push(entry.key.span);
-    }
-
-    for (key, spans) in strings.iter() {
-        if spans.len() == 1 {
-            continue;
-        }
-
-        dups = true;
-        cx.span_err(sp,
-                format!("duplicate key `{}`", key).as_slice());
-        for span in spans.iter() {
-            cx.span_note(*span, "one occurrence here");
+        match originals.find(&entry.key_str) {
+            Some(&span) => {
+                dups = true;
+                cx.span_err(entry.key.span,
+                            format!("redefinition of key `{}`",
+                                    entry.key_str).as_slice());
+                cx.span_note(span,
+                             format!("original definition of key `{}` here",
+                                     entry.key_str).as_slice());
+                continue;
+            }
+            None => {}
         }
+        originals.insert(entry.key_str.clone(), entry.key.span);
     }
 
     dups
 }
 
-fn generate_hash(cx: &mut ExtCtxt, sp: Span, entries: &[Entry]) -> HashState {
-    let mut rng: XorShiftRng = SeedableRng::from_seed(FIXED_SEED);
-    let start = time::precise_time_s();
-    let state;
-    loop {
-        match try_generate_hash(entries.as_slice(), &mut rng) {
-            Some(s) => {
-                state = s;
-                break;
+// A value used only by definitions dropped by `on_duplicate`, paired with the
+// value kept for the same key
+type DroppedValue = (@Expr, @Expr);
+
+// Reports duplicate keys or removes them, as chosen by the `on_duplicate`
+// option.
+fn apply_duplicate_policy(cx: &mut ExtCtxt, map: ParsedMap)
+                          -> Option<(ParsedMap, Vec<DroppedValue>)> {
+    match map.options.on_duplicate {
+        ErrorOnDuplicate => {
+            if has_duplicates(cx, map.entries.as_slice()) {
+                None
+            } else {
+                Some((map, vec![]))
             }
-            None => {}
         }
+        KeepFirst | KeepLast => Some(remove_duplicates(map)),
     }
-    let time = time::precise_time_s() - start;
-    if os::getenv("PHF_STATS").is_some() {
-        cx.span_note(sp, format!("PHF generation took {} seconds", time)
-                            .as_slice());
-    }
-
-    state
 }
 
-fn try_generate_hash(entries: &[Entry], rng: &mut XorShiftRng)
-                     -> Option<HashState> {
-    struct Bucket {
-        idx: uint,
-        keys: Vec<uint>,
+// Keeps only the first or last definition of each key, as chosen by the
+// `on_duplicate` option. The values no longer used by any entry are removed
+// from the map and returned, so that they can still be type checked.
+fn remove_duplicates(map: ParsedMap) -> (ParsedMap, Vec<DroppedValue>) {
+    let ParsedMap { entries: all_entries, values, options } = map;
+
+    let mut kept = HashMap::new();
+    for (i, entry) in all_entries.iter().enumerate() {
+        if options.on_duplicate == KeepLast ||
+                !kept.contains_key(&entry.key_str) {
+            kept.insert(entry.key_str.clone(), i);
+        }
     }
 
-    struct Hashes {
-        g: uint,
-        f1: uint,
-        f2: uint,
+    let mut entries = vec![];
+    let mut dropped_entries = vec![];
+    for (i, entry) in all_entries.move_iter().enumerate() {
+        if *kept.get(&entry.key_str) == i {
+            entries.push(entry);
+        } else {
+            dropped_entries.push(entry);
+        }
     }
 
-    let k1 = rng.gen();
-    let k2 = rng.gen();
+    let mut remap = Vec::from_elem(values.len(), None);
+    let mut used = vec![];
+    let mut kept_values = HashMap::new();
+    for entry in entries.mut_iter() {
+        entry.value = match *remap.get(entry.value) {
+            Some(idx) => idx,
+            None => {
+                let idx = used.len();
+                used.push(*values.get(entry.value));
+                *remap.get_mut(entry.value) = Some(idx);
+                idx
+            }
+        };
+        kept_values.insert(entry.key_str.clone(), entry.value);
+    }
 
-    let hashes: Vec<Hashes> = entries.iter().map(|entry| {
-        let (g, f1, f2) = phf::hash(entry.key_str.get(), k1, k2);
-        Hashes {
-            g: g,
-            f1: f1,
-            f2: f2
+    let mut checked = Vec::from_elem(values.len(), false);
+    let mut dropped = vec![];
+    for entry in dropped_entries.iter() {
+        if remap.get(entry.value).is_some() || *checked.get(entry.value) {
+            continue;
         }
-    }).collect();
+        *checked.get_mut(entry.value) = true;
+        let kept_value = *used.get(*kept_values.get(&entry.key_str));
+        dropped.push((kept_value, *values.get(entry.value)));
+    }
 
-    let buckets_len = (entries.len() + DEFAULT_LAMBDA - 1) / DEFAULT_LAMBDA;
-    let mut buckets = Vec::from_fn(buckets_len,
-                                   |i| Bucket { idx: i, keys: Vec::new() });
-
-    for (i, hash) in hashes.iter().enumerate() {
-        buckets.get_mut(hash.g % buckets_len).keys.push(i);
-    }
-
-    // Sort descending
-    buckets.sort_by(|a, b| b.keys.len().cmp(&a.keys.len()));
-
-    let table_len = entries.len();
-    let mut map = Vec::from_elem(table_len, None);
-    let mut disps = Vec::from_elem(buckets_len, (0u, 0u));
-    let mut try_map = HashMap::new();
-    'buckets: for bucket in buckets.iter() {
-        for d1 in range(0, table_len) {
-            'disps_l: for d2 in range(0, table_len) {
-                try_map.clear();
-                for &key in bucket.keys.iter() {
-                    let idx = phf::displace(hashes.get(key).f1,
-                                            hashes.get(key).f2,
-                                            d1,
-                                            d2) % table_len;
-                    if map.get(idx).is_some() || try_map.find(&idx).is_some() {
-                        continue 'disps_l;
-                    }
-                    try_map.insert(idx, key);
-                }
+    let map = ParsedMap {
+        entries: entries,
+        values: used,
+        options: options,
+    };
+    (map, dropped)
+}
 
-                // We've picked a good set of disps
-                *disps.get_mut(bucket.idx) = (d1, d2);
-                for (&idx, &key) in try_map.iter() {
-                    *map.get_mut(idx) = Some(key);
-                }
-                continue 'buckets;
-            }
-        }
+// Sorts the entries by key. Values keep their positions, so the entries still
+// refer to the right ones.
+fn sort_entries(map: ParsedMap) -> ParsedMap {
+    let ParsedMap { mut entries, values, options } = map;
+    entries.sort_by(|a, b| a.key_str.get().cmp(&b.key_str.get()));
+    ParsedMap {
+        entries: entries,
+        values: values,
+        options: options,
+    }
+}
 
-        // Unable to find displacements for a bucket
-        return None;
+fn generate_hash(cx: &mut ExtCtxt, sp: Span, entries: &[Entry]) -> HashState {
+    let keys = entries.iter().map(|e| e.key_str.get()).collect::<Vec<&str>>();
+    time_generation(cx, sp, || phf_codegen::generate_hash(keys.as_slice()))
+}
+
+// Generates the hash of a table which may have been given a seed. Generation
+// with a seed can fail.
+fn generate_table_hash(cx: &mut ExtCtxt, sp: Span, map: &ParsedMap)
+                       -> Option<HashState> {
+    let seed = match map.options.seed {
+        Some(seed) => seed,
+        None => return Some(generate_hash(cx, sp, map.entries.as_slice())),
+    };
+
+    let keys = map.entries.iter().map(|e| e.key_str.get())
+                          .collect::<Vec<&str>>();
+    let state = time_generation(cx, sp, || {
+        phf_codegen::generate_hash_with_seed(keys.as_slice(), seed)
+    });
+    if state.is_none() {
+        cx.span_err(sp, format!("unable to generate a hash with seed {}; try \
+                                 another seed", seed).as_slice());
     }
+    state
+}
 
-    Some(HashState {
-        k1: k1,
-        k2: k2,
-        disps: disps,
-        map: map.move_iter().map(|i| i.unwrap()).collect(),
-    })
+// The slot of the table holding each key
+fn slot_indices(entries: &[Entry], state: &HashState) -> HashMap<String, uint> {
+    state.map.iter().enumerate().map(|(slot, &idx)| {
+        (entries[idx].key_str.get().to_string(), slot)
+    }).collect()
+}
+
+fn time_generation<T>(cx: &mut ExtCtxt, sp: Span, generate: || -> T) -> T {
+    let start = time::precise_time_s();
+    let state = generate();
+    let time = time::precise_time_s() - start;
+    if os::getenv("PHF_STATS").is_some() {
+        cx.span_note(sp, format!("PHF generation took {} seconds", time)
+                            .as_slice());
+    }
+
+    state
 }
 
-fn create_map(cx: &mut ExtCtxt, sp: Span, entries: Vec<Entry>, state: HashState)
-              -> Box<MacResult> {
+fn create_map(cx: &mut ExtCtxt, sp: Span, map: ParsedMap, state: HashState)
+              -> @Expr {
     let disps = state.disps.iter().map(|&(d1, d2)| {
         quote_expr!(&*cx, ($d1, $d2))
     }).collect();
     let disps = create_slice_expr(disps, sp);
 
     let entries = state.map.iter().map(|&idx| {
-        let &Entry { key, value, .. } = entries.get(idx);
+        let &Entry { key, value, .. } = map.entries.get(idx);
         quote_expr!(&*cx, ($key, $value))
     }).collect();
     let entries = create_slice_expr(entries, sp);
 
+    let lens = create_lens_expr(cx, sp, &map);
+    let values = create_slice_expr(map.values, sp);
+    let (len_idxs, len_buckets) =
+        create_len_index_exprs(cx, sp, map.entries.as_slice(),
+                               state.map.as_slice());
+
     let k1 = state.k1;
     let k2 = state.k2;
-    MacExpr::new(quote_expr!(cx, ::phf::PhfMap {
+    quote_expr!(cx, ::phf::PhfMap {
         k1: $k1,
         k2: $k2,
         disps: &'static $disps,
         entries: &'static $entries,
-    }))
+        values: &'static $values,
+        lens: &'static $lens,
+        len_idxs: &'static $len_idxs,
+        len_buckets: &'static $len_buckets,
+    })
 }
 
-fn create_set(cx: &mut ExtCtxt, sp: Span, entries: Vec<Entry>, state: HashState)
-              -> Box<MacResult> {
-    let map = create_map(cx, sp, entries, state).make_expr().unwrap();
-    MacExpr::new(quote_expr!(cx, ::phf::PhfSet { map: $map }))
+fn create_set(cx: &mut ExtCtxt, sp: Span, map: ParsedMap, state: HashState)
+              -> @Expr {
+    let map = create_map(cx, sp, map, state);
+    quote_expr!(cx, ::phf::PhfSet { map: $map })
 }
 
-fn create_ordered_map(cx: &mut ExtCtxt, sp: Span, entries: Vec<Entry>,
-                      state: HashState) -> Box<MacResult> {
+fn create_ordered_map(cx: &mut ExtCtxt, sp: Span, map: ParsedMap,
+                      state: HashState) -> @Expr {
     let disps = state.disps.iter().map(|&(d1, d2)| {
         quote_expr!(&*cx, ($d1, $d2))
     }).collect();
@@ -391,26 +1929,429 @@ fn create_ordered_map(cx: &mut ExtCtxt, sp: Span, entries: Vec<Entry>,
     let idxs = state.map.iter().map(|&idx| quote_expr!(&*cx, $idx)).collect();
     let idxs = create_slice_expr(idxs, sp);
 
-    let entries = entries.iter().map(|&Entry { key, value, .. }| {
+    let entries = map.entries.iter().map(|&Entry { key, value, .. }| {
         quote_expr!(&*cx, ($key, $value))
     }).collect();
     let entries = create_slice_expr(entries, sp);
 
+    let lens = create_lens_expr(cx, sp, &map);
+    let values = create_slice_expr(map.values, sp);
+    let order = Vec::from_fn(map.entries.len(), |i| i);
+    let (len_idxs, len_buckets) =
+        create_len_index_exprs(cx, sp, map.entries.as_slice(),
+                               order.as_slice());
+
     let k1 = state.k1;
     let k2 = state.k2;
-    MacExpr::new(quote_expr!(cx, ::phf::PhfOrderedMap {
+    quote_expr!(cx, ::phf::PhfOrderedMap {
         k1: $k1,
         k2: $k2,
         disps: &'static $disps,
         idxs: &'static $idxs,
         entries: &'static $entries,
+        values: &'static $values,
+        lens: &'static $lens,
+        len_idxs: &'static $len_idxs,
+        len_buckets: &'static $len_buckets,
+    })
+}
+
+fn create_ordered_set(cx: &mut ExtCtxt, sp: Span, map: ParsedMap,
+                      state: HashState) -> @Expr {
+    let map = create_ordered_map(cx, sp, map, state);
+    quote_expr!(cx, ::phf::PhfOrderedSet { map: $map })
+}
+
+fn create_sorted_map(cx: &mut ExtCtxt, sp: Span, map: ParsedMap,
+                     state: HashState) -> @Expr {
+    let map = create_ordered_map(cx, sp, map, state);
+    quote_expr!(cx, ::phf::PhfSortedMap { map: $map })
+}
+
+fn create_sorted_set(cx: &mut ExtCtxt, sp: Span, map: ParsedMap,
+                     state: HashState) -> @Expr {
+    let map = create_sorted_map(cx, sp, map, state);
+    quote_expr!(cx, ::phf::PhfSortedSet { map: $map })
+}
+
+// A node of the trie of the keys of a search map
+struct TrieNode {
+    // The byte and target of each edge out of the node
+    edges: Vec<(u8, uint)>,
+    // The node for the longest proper suffix of this node's string which is
+    // also in the trie
+    fail: uint,
+    depth: uint,
+    // The position in the map's entries of the key equal to this node's string
+    entry: Option<uint>,
+    // The nearest node along the failure links which has an entry, or the root
+    next_output: uint,
+}
+
+impl TrieNode {
+    fn new(depth: uint) -> TrieNode {
+        TrieNode {
+            edges: vec![],
+            fail: 0,
+            depth: depth,
+            entry: None,
+            next_output: 0,
+        }
+    }
+}
+
+fn trie_edge(nodes: &[TrieNode], node: uint, byte: u8) -> Option<uint> {
+    nodes[node].edges.iter().find(|&&(b, _)| b == byte).map(|&(_, t)| t)
+}
+
+// Follows the edge for `byte` out of `node`, falling back along failure links
+// until one exists, as lookups do.
+fn trie_step(nodes: &[TrieNode], mut node: uint, byte: u8) -> uint {
+    loop {
+        match trie_edge(nodes, node, byte) {
+            Some(next) => return next,
+            None if node == 0 => return 0,
+            None => node = nodes[node].fail,
+        }
+    }
+}
+
+fn create_search_map(cx: &mut ExtCtxt, sp: Span, map: ParsedMap,
+                     state: HashState) -> Option<@Expr> {
+    let mut bad = false;
+    for entry in map.entries.iter() {
+        if entry.key_str.get().is_empty() {
+            cx.span_err(entry.key.span,
+                        "keys of a search map may not be empty");
+            bad = true;
+        }
+    }
+    if bad {
+        return None;
+    }
+
+    // Outputs refer to entries by their position in the map
+    let mut positions = Vec::from_elem(map.entries.len(), 0u);
+    for (slot, &idx) in state.map.iter().enumerate() {
+        *positions.get_mut(idx) = slot;
+    }
+
+    let mut nodes = vec![TrieNode::new(0)];
+    for (i, entry) in map.entries.iter().enumerate() {
+        let mut node = 0;
+        for &byte in entry.key_str.get().as_bytes().iter() {
+            node = match trie_edge(nodes.as_slice(), node, byte) {
+                Some(next) => next,
+                None => {
+                    let next = nodes.len();
+                    let depth = nodes.get(node).depth + 1;
+                    nodes.push(TrieNode::new(depth));
+                    nodes.get_mut(node).edges.push((byte, next));
+                    next
+                }
+            };
+        }
+        nodes.get_mut(node).entry = Some(*positions.get(i));
+    }
+
+    // Failure links point to shallower nodes, so they are filled in breadth
+    // first
+    let mut queue = RingBuf::new();
+    for &(_, child) in nodes.get(0).edges.iter() {
+        queue.push_back(child);
+    }
+    loop {
+        let node = match queue.pop_front() {
+            Some(node) => node,
+            None => break,
+        };
+
+        let edges = nodes.get(node).edges.clone();
+        for &(byte, child) in edges.iter() {
+            let fail = trie_step(nodes.as_slice(), nodes.get(node).fail, byte);
+            let next_output = match nodes.get(fail).entry {
+                Some(_) => fail,
+                None => nodes.get(fail).next_output,
+            };
+            let child_node = nodes.get_mut(child);
+            child_node.fail = fail;
+            child_node.next_output = next_output;
+            queue.push_back(child);
+        }
+    }
+
+    let mut edges = vec![];
+    let mut states = vec![];
+    let mut outputs = vec![];
+    for node in nodes.mut_iter() {
+        node.edges.sort();
+        let lo = edges.len();
+        for &(byte, target) in node.edges.iter() {
+            edges.push(quote_expr!(&*cx, ($byte, $target)));
+        }
+        let hi = edges.len();
+        let fail = node.fail;
+        let depth = node.depth;
+        states.push(quote_expr!(&*cx, ($lo, $hi, $fail, $depth)));
+
+        let entry = node.entry.map_or(0, |entry| entry + 1);
+        let next_output = node.next_output;
+        outputs.push(quote_expr!(&*cx, ($entry, $next_output)));
+    }
+    let edges = create_slice_expr(edges, sp);
+    let states = create_slice_expr(states, sp);
+    let outputs = create_slice_expr(outputs, sp);
+
+    let map = create_map(cx, sp, map, state);
+    Some(quote_expr!(cx, ::phf::PhfSearchMap {
+        map: $map,
+        edges: &'static $edges,
+        states: &'static $states,
+        outputs: &'static $outputs,
+    }))
+}
+
+fn create_multimap(cx: &mut ExtCtxt, sp: Span, map: ParsedMap)
+                   -> Option<(@Expr, MapInfo)> {
+    let ParsedMap { entries, values, options } = map;
+
+    // Group the values by key, with the keys in the order they first appear
+    let mut keys: Vec<Entry> = vec![];
+    let mut groups: Vec<Vec<@Expr>> = vec![];
+    let mut group_idxs = HashMap::new();
+    for entry in entries.move_iter() {
+        let value = *values.get(entry.value);
+        let idx = match group_idxs.find(&entry.key_str) {
+            Some(&idx) => idx,
+            None => {
+                let idx = groups.len();
+                groups.push(vec![]);
+                keys.push(Entry {
+                    key_str: entry.key_str.clone(),
+                    key: entry.key,
+                    value: idx,
+                });
+                idx
+            }
+        };
+        group_idxs.insert(entry.key_str, idx);
+        groups.get_mut(idx).push(value);
+    }
+
+    let mut ranges = vec![];
+    let mut flattened = vec![];
+    for group in groups.move_iter() {
+        let start = flattened.len();
+        flattened.push_all_move(group);
+        let end = flattened.len();
+        ranges.push(quote_expr!(&*cx, ($start, $end)));
+    }
+
+    let map = ParsedMap {
+        entries: keys,
+        values: ranges,
+        options: options,
+    };
+    let state = match generate_table_hash(cx, sp, &map) {
+        Some(state) => state,
+        None => return None,
+    };
+
+    let info = MapInfo {
+        kind: MultiMapKind,
+        values: map.entries.iter().map(|entry| {
+            (entry.key_str.get().to_string(), entry.value)
+        }).collect(),
+        indices: slot_indices(map.entries.as_slice(), &state),
+    };
+    let map = create_map(cx, sp, map, state);
+    let values = create_slice_expr(flattened, sp);
+
+    let expr = quote_expr!(cx, ::phf::PhfMultiMap {
+        map: $map,
+        values: &'static $values,
+    });
+    Some((expr, info))
+}
+
+// A value of a `PhfBiMap`, as it is hashed for lookups by value
+#[deriving(PartialEq, Eq, Hash, Clone)]
+enum BiValue {
+    IntValue(u64),
+    StrValue(InternedString),
+}
+
+fn parse_bi_value(e: &Expr) -> Option<BiValue> {
+    match e.node {
+        ExprLit(lit) => {
+            match lit.node {
+                LitStr(ref s, _) => Some(StrValue(s.clone())),
+                LitInt(i, _) | LitIntUnsuffixed(i) => Some(IntValue(i as u64)),
+                LitUint(u, _) => Some(IntValue(u)),
+                LitChar(c) => Some(IntValue(c as u64)),
+                LitBool(b) => Some(IntValue(b as u64)),
+                _ => None,
+            }
+        }
+        ExprUnary(UnNeg, inner) => {
+            match parse_bi_value(inner) {
+                Some(IntValue(i)) => Some(IntValue(-(i as i64) as u64)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+fn create_bimap(cx: &mut ExtCtxt, sp: Span, map: ParsedMap, state: HashState)
+                -> Option<@Expr> {
+    let mut bad = false;
+    let mut parsed = vec![];
+    for value in map.values.iter() {
+        let parsed_value = parse_bi_value(*value);
+        if parsed_value.is_none() {
+            cx.span_err(value.span, "values of a `PhfBiMap` must be string, \
+                                     integer, character or boolean literals");
+            bad = true;
+        }
+        parsed.push(parsed_value);
+    }
+    if bad {
+        return None;
+    }
+
+    // Each value may only be used by one key
+    let mut value_owners = Vec::from_elem(map.values.len(), None);
+    let mut owners = HashMap::new();
+    for entry in map.entries.iter() {
+        let value = parsed.get(entry.value).get_ref();
+        match *value_owners.get(entry.value) {
+            Some(ref owner) => {
+                cx.span_err(entry.key.span,
+                            format!("key `{}` shares its value with key `{}`, \
+                                     but the values of a `PhfBiMap` must be \
+                                     unique", entry.key_str, owner)
+                                   .as_slice());
+                bad = true;
+                continue;
+            }
+            None => {}
+        }
+        match owners.find(value) {
+            Some(owner) => {
+                cx.span_err(map.values.get(entry.value).span,
+                            format!("the value of key `{}` is already used by \
+                                     key `{}`", entry.key_str, owner)
+                                   .as_slice());
+                bad = true;
+                continue;
+            }
+            None => {}
+        }
+        *value_owners.get_mut(entry.value) = Some(entry.key_str.clone());
+        owners.insert(value.clone(), entry.key_str.clone());
+    }
+    if bad {
+        return None;
+    }
+
+    let values = map.entries.iter().map(|entry| {
+        parsed.get(entry.value).get_ref()
+    }).collect::<Vec<&BiValue>>();
+    let ints = values.iter().filter_map(|value| {
+        match **value {
+            IntValue(i) => Some(i),
+            StrValue(_) => None,
+        }
+    }).collect::<Vec<u64>>();
+    let strs = values.iter().filter_map(|value| {
+        match **value {
+            IntValue(_) => None,
+            StrValue(ref s) => Some(s.get()),
+        }
+    }).collect::<Vec<&str>>();
+    let reverse = if strs.is_empty() {
+        time_generation(cx, sp, || phf_codegen::generate_hash(ints.as_slice()))
+    } else if ints.is_empty() {
+        time_generation(cx, sp, || phf_codegen::generate_hash(strs.as_slice()))
+    } else {
+        cx.span_err(sp, "the values of a `PhfBiMap` must all have the same \
+                         type");
+        return None;
+    };
+
+    // The reverse table refers to entries by their position in the map
+    let mut positions = Vec::from_elem(map.entries.len(), 0u);
+    for (slot, &idx) in state.map.iter().enumerate() {
+        *positions.get_mut(idx) = slot;
+    }
+
+    let disps = reverse.disps.iter().map(|&(d1, d2)| {
+        quote_expr!(&*cx, ($d1, $d2))
+    }).collect();
+    let disps = create_slice_expr(disps, sp);
+
+    let idxs = reverse.map.iter().map(|&idx| {
+        let position = *positions.get(idx);
+        quote_expr!(&*cx, $position)
+    }).collect();
+    let idxs = create_slice_expr(idxs, sp);
+
+    let map = create_map(cx, sp, map, state);
+
+    let k1 = reverse.k1;
+    let k2 = reverse.k2;
+    Some(quote_expr!(cx, ::phf::PhfBiMap {
+        map: $map,
+        k1: $k1,
+        k2: $k2,
+        disps: &'static $disps,
+        idxs: &'static $idxs,
     }))
 }
 
-fn create_ordered_set(cx: &mut ExtCtxt, sp: Span, entries: Vec<Entry>,
-                      state: HashState) -> Box<MacResult> {
-    let map = create_ordered_map(cx, sp, entries, state).make_expr().unwrap();
-    MacExpr::new(quote_expr!(cx, ::phf::PhfOrderedSet { map: $map }))
+// The distinct lengths of the keys, longest first, for `find_longest_prefix`.
+// The table is left empty unless the `longest_prefix` option was given.
+fn create_lens_expr(cx: &mut ExtCtxt, sp: Span, map: &ParsedMap) -> @Expr {
+    let mut lens: Vec<uint> = if map.options.longest_prefix.is_some() {
+        map.entries.iter().map(|e| e.key_str.get().len()).collect()
+    } else {
+        vec![]
+    };
+    lens.sort_by(|a, b| b.cmp(a));
+    lens.dedup();
+
+    let lens = lens.move_iter().map(|len| quote_expr!(&*cx, $len)).collect();
+    create_slice_expr(lens, sp)
+}
+
+// The positions of the entries in `order`, grouped into buckets by the number
+// of characters in their keys, and the length and end of each bucket, for
+// `suggest`
+fn create_len_index_exprs(cx: &mut ExtCtxt, sp: Span, entries: &[Entry],
+                          order: &[uint]) -> (@Expr, @Expr) {
+    let key_lens = order.iter().map(|&idx| {
+        entries[idx].key_str.get().char_len()
+    }).collect::<Vec<uint>>();
+
+    let mut len_idxs = Vec::from_fn(order.len(), |i| i);
+    len_idxs.sort_by(|&a, &b| key_lens.get(a).cmp(key_lens.get(b)));
+
+    let mut len_buckets: Vec<(uint, uint)> = Vec::new();
+    for (i, &idx) in len_idxs.iter().enumerate() {
+        let len = *key_lens.get(idx);
+        if len_buckets.last().map_or(false, |&(last, _)| last == len) {
+            len_buckets.pop();
+        }
+        len_buckets.push((len, i + 1));
+    }
+
+    let len_idxs = len_idxs.move_iter().map(|idx| quote_expr!(&*cx, $idx))
+                           .collect();
+    let len_buckets = len_buckets.move_iter().map(|(len, end)| {
+        quote_expr!(&*cx, ($len, $end))
+    }).collect();
+    (create_slice_expr(len_idxs, sp), create_slice_expr(len_buckets, sp))
 }
 
 fn create_slice_expr(vec: Vec<@Expr>, sp: Span) -> @Expr {
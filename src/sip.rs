@@ -0,0 +1,66 @@
+//! A self-contained implementation of SipHash-2-4.
+//!
+//! `libstd`'s hasher is not available to `no_std` users, and lookups must hash
+//! keys exactly the same way the compiler plugin did, so `phf` carries its own.
+
+use core::prelude::*;
+
+macro_rules! compress (
+    ($v0:expr, $v1:expr, $v2:expr, $v3:expr) => ({
+        $v0 += $v1; $v1 = rotl($v1, 13); $v1 ^= $v0; $v0 = rotl($v0, 32);
+        $v2 += $v3; $v3 = rotl($v3, 16); $v3 ^= $v2;
+        $v0 += $v3; $v3 = rotl($v3, 21); $v3 ^= $v0;
+        $v2 += $v1; $v1 = rotl($v1, 17); $v1 ^= $v2; $v2 = rotl($v2, 32);
+    })
+)
+
+#[inline]
+fn rotl(x: u64, b: uint) -> u64 {
+    (x << b) | (x >> (64 - b))
+}
+
+/// Reads `len` bytes of `buf` starting at `start` as a little endian integer.
+#[inline]
+fn read_le(buf: &[u8], start: uint, len: uint) -> u64 {
+    let mut out = 0u64;
+    for i in range(0, len) {
+        out |= (buf[start + i] as u64) << (8 * i);
+    }
+    out
+}
+
+/// Computes the SipHash-2-4 of `bytes` with the keys `k0` and `k1`.
+pub fn hash(k0: u64, k1: u64, bytes: &[u8]) -> u64 {
+    let mut v0 = k0 ^ 0x736f6d6570736575;
+    let mut v1 = k1 ^ 0x646f72616e646f6d;
+    let mut v2 = k0 ^ 0x6c7967656e657261;
+    let mut v3 = k1 ^ 0x7465646279746573;
+
+    let len = bytes.len();
+    let tail = len & 7;
+    let end = len - tail;
+
+    let mut i = 0;
+    while i < end {
+        let m = read_le(bytes, i, 8);
+        v3 ^= m;
+        compress!(v0, v1, v2, v3);
+        compress!(v0, v1, v2, v3);
+        v0 ^= m;
+        i += 8;
+    }
+
+    let b = ((len as u64 & 0xff) << 56) | read_le(bytes, end, tail);
+    v3 ^= b;
+    compress!(v0, v1, v2, v3);
+    compress!(v0, v1, v2, v3);
+    v0 ^= b;
+
+    v2 ^= 0xff;
+    compress!(v0, v1, v2, v3);
+    compress!(v0, v1, v2, v3);
+    compress!(v0, v1, v2, v3);
+    compress!(v0, v1, v2, v3);
+
+    v0 ^ v1 ^ v2 ^ v3
+}
@@ -0,0 +1,79 @@
+//! Suggestions of keys close to a misspelled one.
+//!
+//! Keys are compared by their optimal string alignment distance: the number of
+//! characters which must be inserted, deleted, substituted or swapped with
+//! their neighbour to turn one into the other, editing each substring at most
+//! once.
+
+use core::prelude::*;
+use core::cmp;
+use core::mem;
+use std::vec::Vec;
+
+/// Returns the optimal string alignment distance between `a` and `b`.
+pub fn distance(a: &str, b: &str) -> uint {
+    let a = a.chars().collect::<Vec<char>>();
+    let b = b.chars().collect::<Vec<char>>();
+
+    // The last three rows of the distance matrix
+    let mut before = Vec::from_elem(b.len() + 1, 0u);
+    let mut prev = Vec::from_fn(b.len() + 1, |j| j);
+    let mut cur = Vec::from_elem(b.len() + 1, 0u);
+
+    for i in range(1, a.len() + 1) {
+        *cur.get_mut(0) = i;
+        for j in range(1, b.len() + 1) {
+            let cost = if a.get(i - 1) == b.get(j - 1) { 0 } else { 1 };
+            let mut d = cmp::min(*prev.get(j) + 1, *cur.get(j - 1) + 1);
+            d = cmp::min(d, *prev.get(j - 1) + cost);
+            if i > 1 && j > 1 && a.get(i - 1) == b.get(j - 2) &&
+                    a.get(i - 2) == b.get(j - 1) {
+                d = cmp::min(d, *before.get(j - 2) + 1);
+            }
+            *cur.get_mut(j) = d;
+        }
+
+        mem::swap(&mut before, &mut prev);
+        mem::swap(&mut prev, &mut cur);
+    }
+
+    *prev.get(b.len())
+}
+
+/// Returns the keys within `max_distance` of `key`, closest first.
+///
+/// `len_idxs` holds the indices of the entries grouped into buckets of keys
+/// with the same length in characters, and `len_buckets` holds the length of
+/// the keys of each bucket and the position in `len_idxs` at which it ends, in
+/// ascending order of length. Keys whose length differs from that of `key` by
+/// more than `max_distance` can't be close enough, so only the buckets of the
+/// others are visited.
+pub fn suggest(entries: &[(&'static str, uint)], len_idxs: &[uint],
+               len_buckets: &[(uint, uint)], key: &str, max_distance: uint)
+               -> Vec<&'static str> {
+    let len = key.char_len();
+
+    let mut found = Vec::new();
+    let mut start = 0;
+    for &(bucket_len, end) in len_buckets.iter() {
+        let bucket = start;
+        start = end;
+        if bucket_len + max_distance < len {
+            continue;
+        }
+        if bucket_len > len + max_distance {
+            break;
+        }
+
+        for &idx in len_idxs.slice(bucket, end).iter() {
+            let (candidate, _) = entries[idx];
+            let d = distance(key, candidate);
+            if d <= max_distance {
+                found.push((d, candidate));
+            }
+        }
+    }
+
+    found.sort();
+    found.move_iter().map(|(_, candidate)| candidate).collect()
+}
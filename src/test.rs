@@ -3,10 +3,76 @@
 #[phase(syntax)]
 extern crate phf_mac;
 extern crate phf;
+extern crate phf_codegen;
+extern crate test;
+
+use std::mem;
+
+// Leaks `vec` to make a table out of it
+fn leak<T>(vec: Vec<T>) -> &'static [T] {
+    unsafe {
+        let slice = mem::transmute(vec.as_slice());
+        mem::forget(vec);
+        slice
+    }
+}
+
+mod sip {
+    use phf;
+
+    // The reference vectors of the SipHash paper: the key is the bytes 0 to
+    // 15, and the message of vector `i` is the bytes 0 to `i - 1`.
+    static VECTORS: [u64, ..64] = [
+        0x726fdb47dd0e0e31, 0x74f839c593dc67fd,
+        0x0d6c8009d9a94f5a, 0x85676696d7fb7e2d,
+        0xcf2794e0277187b7, 0x18765564cd99a68d,
+        0xcbc9466e58fee3ce, 0xab0200f58b01d137,
+        0x93f5f5799a932462, 0x9e0082df0ba9e4b0,
+        0x7a5dbbc594ddb9f3, 0xf4b32f46226bada7,
+        0x751e8fbc860ee5fb, 0x14ea5627c0843d90,
+        0xf723ca908e7af2ee, 0xa129ca6149be45e5,
+        0x3f2acc7f57c29bdb, 0x699ae9f52cbe4794,
+        0x4bc1b3f0968dd39c, 0xbb6dc91da77961bd,
+        0xbed65cf21aa2ee98, 0xd0f2cbb02e3b67c7,
+        0x93536795e3a33e88, 0xa80c038ccd5ccec8,
+        0xb8ad50c6f649af94, 0xbce192de8a85b8ea,
+        0x17d835b85bbb15f3, 0x2f2e6163076bcfad,
+        0xde4daaaca71dc9a5, 0xa6a2506687956571,
+        0xad87a3535c49ef28, 0x32d892fad841c342,
+        0x7127512f72f27cce, 0xa7f32346f95978e3,
+        0x12e0b01abb051238, 0x15e034d40fa197ae,
+        0x314dffbe0815a3b4, 0x027990f029623981,
+        0xcadcd4e59ef40c4d, 0x9abfd8766a33735c,
+        0x0e3ea96b5304a7d0, 0xad0c42d6fc585992,
+        0x187306c89bc215a9, 0xd4a60abcf3792b95,
+        0xf935451de4f21df2, 0xa9538f0419755787,
+        0xdb9acddff56ca510, 0xd06c98cd5c0975eb,
+        0xe612a3cb9ecba951, 0xc766e62cfcadaf96,
+        0xee64435a9752fe72, 0xa192d576b245165a,
+        0x0a8787bf8ecb74b2, 0x81b3e73d20b49b6f,
+        0x7fa8220ba3b2ecea, 0x245731c13ca42499,
+        0xb78dbfaf3a8d83bd, 0xea1ad565322a1a0b,
+        0x60e61c23a3795013, 0x6606d7e446282b93,
+        0x6ca4ecb15c5f91e1, 0x9f626da15c9625f3,
+        0xe51b38608ef25f57, 0x958a324ceb064572,
+    ];
+
+    #[test]
+    fn test_vectors() {
+        let k0 = 0x0706050403020100;
+        let k1 = 0x0f0e0d0c0b0a0908;
+        let mut msg = vec![];
+        for (i, &expected) in VECTORS.iter().enumerate() {
+            assert_eq!(expected, phf::sip_hash(k0, k1, msg.as_slice()));
+            msg.push(i as u8);
+        }
+    }
+}
 
 mod map {
     use std::collections::{HashMap, HashSet};
-    use phf::PhfMap;
+    use phf::{PhfMap, NoDisplacements, NoEntries, ValueOutOfBounds,
+              MisplacedKey, MissingLength, UnsortedLengths, BadLengthIndex};
 
     #[allow(dead_code)]
     static TRAILING_COMMA: PhfMap<int> = phf_map!(
@@ -30,6 +96,56 @@ mod map {
         assert_eq!(2, map.len());
     }
 
+    #[test]
+    fn test_empty() {
+        static map: PhfMap<int> = phf_map!();
+        assert_eq!(None, map.find(&("foo")));
+        assert_eq!(None, map.find_key(&("foo")));
+        assert_eq!(0, map.len());
+        assert!(map.entries().next().is_none());
+        assert!(map.keys().next().is_none());
+        assert!(map.values().next().is_none());
+        assert_eq!("{}".to_string(), format!("{}", map));
+        assert!(Ok(()) == map.validate());
+    }
+
+    fn sorted_values(map: &PhfMap<int>) -> Vec<int> {
+        let mut values = map.values().map(|&v| v).collect::<Vec<_>>();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn test_on_duplicate() {
+        static FIRST: PhfMap<int> = phf_map!(
+            #![on_duplicate = "first"]
+            "foo" => 10,
+            "bar" => 11,
+            "foo" => 12,
+        );
+        assert!(Some(&10) == FIRST.find(&("foo")));
+        assert!(Some(&11) == FIRST.find(&("bar")));
+        assert_eq!(2, FIRST.len());
+        assert_eq!(vec![10, 11], sorted_values(&FIRST));
+
+        static LAST: PhfMap<int> = phf_map!(
+            #![on_duplicate = "last"]
+            "foo" => 10,
+            "bar" | "foo" => 11,
+            "foo" => 12,
+        );
+        assert!(Some(&12) == LAST.find(&("foo")));
+        assert!(Some(&11) == LAST.find(&("bar")));
+        assert_eq!(2, LAST.len());
+        assert_eq!(vec![11, 12], sorted_values(&LAST));
+
+        static ERROR: PhfMap<int> = phf_map!(
+            #![on_duplicate = "error"]
+            "foo" => 10,
+        );
+        assert!(Some(&10) == ERROR.find(&("foo")));
+    }
+
     #[test]
     fn test_entries() {
         static map: PhfMap<int> = phf_map!(
@@ -45,6 +161,61 @@ mod map {
         assert_eq!(2, hash.len());
     }
 
+    #[test]
+    fn test_index_of() {
+        static MAP: PhfMap<int> = phf_map!(
+            "foo" => 10,
+            "bar" => 11,
+            "baz" => 12,
+        );
+        let mut seen = HashSet::new();
+        for key in MAP.keys() {
+            let idx = MAP.index_of(&key).unwrap();
+            assert!(idx < MAP.len());
+            assert!(seen.insert(idx));
+            assert_eq!(Some(key), MAP.key_at(idx));
+            assert_eq!(MAP.find(&key).map(|v| (key, v)), MAP.entry_at(idx));
+        }
+        assert_eq!(None, MAP.index_of(&"qux"));
+        assert_eq!(None, MAP.key_at(3));
+        assert_eq!(None, MAP.entry_at(3));
+    }
+
+    #[test]
+    fn test_find_many() {
+        static MAP: PhfMap<int> = phf_map!(
+            "foo" => 10,
+            "bar" => 11,
+            "baz" => 12,
+        );
+        // More keys than are resolved in one batch
+        let names = ["foo", "bar", "qux", "baz", ""];
+        let keys = Vec::from_fn(40, |i| names[i % names.len()]);
+        let mut values = Vec::from_elem(keys.len(), None);
+        MAP.find_many(keys.as_slice(), values.as_mut_slice());
+        for (key, value) in keys.iter().zip(values.iter()) {
+            assert_eq!(MAP.find(key), *value);
+        }
+    }
+
+    #[test]
+    fn test_find_many_empty() {
+        static MAP: PhfMap<int> = phf_map!();
+        let ten = 10;
+        let mut values = [Some(&ten), Some(&ten)];
+        MAP.find_many(&["foo", "bar"], values.as_mut_slice());
+        assert!(values.iter().all(|value| value.is_none()));
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_find_many_mismatch() {
+        static MAP: PhfMap<int> = phf_map!(
+            "foo" => 10,
+        );
+        MAP.find_many(&["foo"], &mut [None, None]);
+    }
+
     #[test]
     fn test_keys() {
         static map: PhfMap<int> = phf_map!(
@@ -115,6 +286,252 @@ mod map {
         );
         assert!(Some(&1) == map.find(&("foobar")));
     }
+
+    #[test]
+    fn test_alternation() {
+        static map: PhfMap<int> = phf_map!(
+            "jpg" | "jpeg" => 1,
+            "png" => 2,
+            "a" | "b" | "c" => 3,
+        );
+        assert!(Some(&1) == map.find(&("jpg")));
+        assert!(Some(&1) == map.find(&("jpeg")));
+        assert!(Some(&2) == map.find(&("png")));
+        assert!(Some(&3) == map.find(&("b")));
+        assert_eq!(Some("jpeg"), map.find_key(&("jpeg")));
+        assert_eq!(6, map.len());
+        assert_eq!(6, map.values().count());
+        assert!(map.find(&"jpg") == map.find(&"jpeg"));
+        assert!(Ok(()) == map.validate());
+    }
+
+    #[test]
+    fn test_cfg() {
+        static map: PhfMap<int> = phf_map!(
+            #[cfg(not(phf_test_cfg))]
+            "foo" => 10,
+            #[cfg(phf_test_cfg)]
+            "foo" => 11,
+            #[cfg(phf_test_cfg)]
+            "bar" => 12,
+            "baz" => 13,
+        );
+        assert!(Some(&10) == map.find(&("foo")));
+        assert_eq!(None, map.find(&("bar")));
+        assert!(Some(&13) == map.find(&("baz")));
+        assert_eq!(2, map.len());
+    }
+
+    #[test]
+    fn test_validate() {
+        static map: PhfMap<int> = phf_map!(
+            "foo" => 10,
+            "bar" => 11,
+        );
+        assert!(Ok(()) == map.validate());
+        map.debug_validate();
+    }
+
+    #[test]
+    fn test_validate_no_disps() {
+        static map: PhfMap<int> = PhfMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [],
+            entries: &'static [("foo", 0)],
+            values: &'static [10],
+            lens: &'static [3],
+            len_idxs: &'static [0],
+            len_buckets: &'static [(3, 1)],
+        };
+        assert!(Err(NoDisplacements) == map.validate());
+    }
+
+    #[test]
+    fn test_validate_no_entries() {
+        static map: PhfMap<int> = PhfMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [(0, 0)],
+            entries: &'static [],
+            values: &'static [],
+            lens: &'static [],
+            len_idxs: &'static [],
+            len_buckets: &'static [],
+        };
+        assert!(Err(NoEntries) == map.validate());
+        assert_eq!(None, map.find(&"foo"));
+    }
+
+    #[test]
+    fn test_validate_value_out_of_bounds() {
+        static map: PhfMap<int> = PhfMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [(0, 0)],
+            entries: &'static [("foo", 1)],
+            values: &'static [10],
+            lens: &'static [3],
+            len_idxs: &'static [0],
+            len_buckets: &'static [(3, 1)],
+        };
+        assert!(Err(ValueOutOfBounds("foo", 1)) == map.validate());
+    }
+
+    #[test]
+    fn test_validate_misplaced() {
+        static map: PhfMap<int> = PhfMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [(0, 0)],
+            entries: &'static [("foo", 0), ("foo", 1)],
+            values: &'static [10, 11],
+            lens: &'static [3],
+            len_idxs: &'static [0, 1],
+            len_buckets: &'static [(3, 2)],
+        };
+        match map.validate() {
+            Err(MisplacedKey("foo", _, _)) => {}
+            r => fail!("unexpected result {}", r),
+        }
+    }
+
+    #[test]
+    fn test_validate_lens() {
+        static MISSING: PhfMap<int> = PhfMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [(0, 0)],
+            entries: &'static [("foo", 0)],
+            values: &'static [10],
+            lens: &'static [2],
+            len_idxs: &'static [0],
+            len_buckets: &'static [(3, 1)],
+        };
+        assert!(Err(MissingLength("foo")) == MISSING.validate());
+
+        static UNSORTED: PhfMap<int> = PhfMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [(0, 0)],
+            entries: &'static [("foo", 0)],
+            values: &'static [10],
+            lens: &'static [3, 4],
+            len_idxs: &'static [0],
+            len_buckets: &'static [(3, 1)],
+        };
+        assert!(Err(UnsortedLengths) == UNSORTED.validate());
+
+        static BAD_INDEX: PhfMap<int> = PhfMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [(0, 0)],
+            entries: &'static [("foo", 0)],
+            values: &'static [10],
+            lens: &'static [3],
+            len_idxs: &'static [1],
+            len_buckets: &'static [(3, 1)],
+        };
+        assert!(Err(BadLengthIndex) == BAD_INDEX.validate());
+
+        static BAD_BUCKET: PhfMap<int> = PhfMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [(0, 0)],
+            entries: &'static [("foo", 0)],
+            values: &'static [10],
+            lens: &'static [3],
+            len_idxs: &'static [0],
+            len_buckets: &'static [(4, 1)],
+        };
+        assert!(Err(BadLengthIndex) == BAD_BUCKET.validate());
+    }
+
+    #[test]
+    fn test_find_longest_prefix() {
+        static OPS: PhfMap<int> = phf_map! {
+            #![longest_prefix]
+            ">" => 1,
+            ">>" => 2,
+            ">>=" => 3,
+            "é" => 4,
+        };
+        assert_eq!(Some((">>=", &3)), OPS.find_longest_prefix(">>=1"));
+        assert_eq!(Some((">>", &2)), OPS.find_longest_prefix(">>1"));
+        assert_eq!(Some((">", &1)), OPS.find_longest_prefix(">=>>"));
+        assert_eq!(Some(("é", &4)), OPS.find_longest_prefix("é>"));
+        assert_eq!(None, OPS.find_longest_prefix("<"));
+        assert_eq!(None, OPS.find_longest_prefix(""));
+        assert!(Ok(()) == OPS.validate());
+    }
+
+    #[test]
+    fn test_find_longest_prefix_undeclared() {
+        static OPS: PhfMap<int> = phf_map! {
+            ">" => 1,
+            ">>" => 2,
+            "é" => 3,
+        };
+        assert_eq!(Some((">>", &2)), OPS.find_longest_prefix(">>>"));
+        assert_eq!(Some((">", &1)), OPS.find_longest_prefix(">="));
+        assert_eq!(Some(("é", &3)), OPS.find_longest_prefix("éé"));
+        assert_eq!(None, OPS.find_longest_prefix("<"));
+        assert_eq!(None, OPS.find_longest_prefix(""));
+    }
+
+    #[test]
+    fn test_suggest() {
+        static WORDS: PhfMap<int> = phf_map! {
+            "color" => 0,
+            "colour" => 1,
+            "collar" => 2,
+            "the" => 3,
+            "then" => 4,
+            "tea" => 5,
+            "é" => 6,
+        };
+        assert_eq!(vec!["colour", "color", "collar"],
+                   WORDS.suggest("colour", 2));
+        assert_eq!(vec!["color"], WORDS.suggest("colr", 1));
+        assert_eq!(vec!["the", "tea", "then"], WORDS.suggest("hte", 2));
+        assert_eq!(vec!["é"], WORDS.suggest("e", 1));
+        assert_eq!(vec!["é", "tea", "the"], WORDS.suggest("", 3));
+        assert!(WORDS.suggest("xyz", 2).is_empty());
+    }
+
+    #[test]
+    fn test_suggest_skips_far_buckets() {
+        // The buckets of keys of 1 and 6 characters refer to entries which
+        // don't exist, so visiting either of them would fail
+        static MAP: PhfMap<int> = PhfMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [(0, 0)],
+            entries: &'static [("foo", 0), ("food", 1)],
+            values: &'static [10, 11],
+            lens: &'static [],
+            len_idxs: &'static [7, 0, 1, 8],
+            len_buckets: &'static [(1, 1), (3, 2), (4, 3), (6, 4)],
+        };
+        assert_eq!(vec!["foo", "food"], MAP.suggest("fod", 1));
+        assert_eq!(vec!["food"], MAP.suggest("food", 0));
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_debug_validate() {
+        static map: PhfMap<int> = PhfMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [],
+            entries: &'static [("foo", 0)],
+            values: &'static [10],
+            lens: &'static [3],
+            len_idxs: &'static [0],
+            len_buckets: &'static [(3, 1)],
+        };
+        map.debug_validate();
+    }
 }
 
 mod set {
@@ -143,6 +560,61 @@ mod set {
         assert_eq!(2, SET.len());
     }
 
+    #[test]
+    fn test_cfg() {
+        static SET: PhfSet = phf_set! {
+            #[cfg(phf_test_cfg)]
+            "hello",
+            #[cfg(not(phf_test_cfg))]
+            "world",
+        };
+        assert!(!SET.contains(&"hello"));
+        assert!(SET.contains(&"world"));
+        assert_eq!(1, SET.len());
+    }
+
+    #[test]
+    fn test_alternation() {
+        static SET: PhfSet = phf_set! {
+            "hello" | "hi",
+            "world",
+        };
+        assert!(SET.contains(&"hello"));
+        assert!(SET.contains(&"hi"));
+        assert!(SET.contains(&"world"));
+        assert_eq!(3, SET.len());
+    }
+
+    #[test]
+    fn test_on_duplicate() {
+        static SET: PhfSet = phf_set! {
+            #![on_duplicate = "first"]
+            "hello",
+            "world",
+            "hello",
+        };
+        assert!(SET.contains(&"hello"));
+        assert!(SET.contains(&"world"));
+        assert_eq!(2, SET.len());
+    }
+
+    #[test]
+    fn test_empty() {
+        static EMPTY: PhfSet = phf_set! {};
+        static SET: PhfSet = phf_set! {
+            "hello",
+        };
+        assert!(!EMPTY.contains(&"hello"));
+        assert_eq!(0, EMPTY.len());
+        assert!(EMPTY.iter().next().is_none());
+        assert_eq!("{}".to_string(), format!("{}", EMPTY));
+        assert!(EMPTY.is_subset(&SET));
+        assert!(!SET.is_subset(&EMPTY));
+        assert!(EMPTY.is_disjoint(&SET));
+        assert!(SET.is_disjoint(&EMPTY));
+        assert!(EMPTY.is_subset(&EMPTY));
+    }
+
     #[test]
     fn test_iter() {
         static SET: PhfSet = phf_set! {
@@ -154,10 +626,25 @@ mod set {
         assert!(set.contains(&"world"));
         assert_eq!(2, set.len());
     }
+
+    #[test]
+    fn test_index_of() {
+        static SET: PhfSet = phf_set! {
+            "foo",
+            "bar",
+            "baz",
+        };
+        for &value in ["foo", "bar", "baz"].iter() {
+            let idx = SET.index_of(&value).unwrap();
+            assert_eq!(Some(value), SET.key_at(idx));
+        }
+        assert_eq!(None, SET.index_of(&"qux"));
+        assert_eq!(None, SET.key_at(3));
+    }
 }
 
 mod ordered_map {
-    use phf::PhfOrderedMap;
+    use phf::{PhfOrderedMap, IndexCountMismatch, IndexOutOfBounds};
 
     #[allow(dead_code)]
     static TRAILING_COMMA: PhfOrderedMap<int> = phf_ordered_map!(
@@ -181,6 +668,22 @@ mod ordered_map {
         assert_eq!(2, map.len());
     }
 
+    #[test]
+    fn test_empty() {
+        static MAP: PhfOrderedMap<int> = phf_ordered_map!();
+        assert_eq!(None, MAP.find(&"foo"));
+        assert_eq!(None, MAP.find_key(&"foo"));
+        assert_eq!(0, MAP.len());
+        assert!(MAP.entries().next().is_none());
+        assert!(MAP.entries().next_back().is_none());
+        assert_eq!(0, MAP.entries().indexable());
+        assert!(MAP.entries().idx(0).is_none());
+        assert!(MAP.keys().idx(0).is_none());
+        assert!(MAP.values().idx(0).is_none());
+        assert_eq!("{}".to_string(), format!("{}", MAP));
+        assert!(Ok(()) == MAP.validate());
+    }
+
     #[test]
     fn test_entries() {
         static MAP: PhfOrderedMap<int> = phf_ordered_map!(
@@ -192,6 +695,38 @@ mod ordered_map {
         assert_eq!(vec, vec!(("foo", 10), ("bar", 11), ("baz", 12)));
     }
 
+    #[test]
+    fn test_index_of() {
+        static MAP: PhfOrderedMap<int> = phf_ordered_map!(
+            "foo" => 10,
+            "bar" => 11,
+            "baz" => 12,
+        );
+        assert_eq!(Some(0), MAP.index_of(&"foo"));
+        assert_eq!(Some(1), MAP.index_of(&"bar"));
+        assert_eq!(Some(2), MAP.index_of(&"baz"));
+        assert_eq!(None, MAP.index_of(&"qux"));
+        assert_eq!(Some("bar"), MAP.key_at(1));
+        assert_eq!(Some(("baz", &12)), MAP.entry_at(2));
+        assert_eq!(None, MAP.entry_at(3));
+    }
+
+    #[test]
+    fn test_find_many() {
+        static MAP: PhfOrderedMap<int> = phf_ordered_map!(
+            "foo" => 10,
+            "bar" => 11,
+            "baz" => 12,
+        );
+        let names = ["foo", "bar", "qux", "baz", ""];
+        let keys = Vec::from_fn(40, |i| names[i % names.len()]);
+        let mut values = Vec::from_elem(keys.len(), None);
+        MAP.find_many(keys.as_slice(), values.as_mut_slice());
+        for (key, value) in keys.iter().zip(values.iter()) {
+            assert_eq!(MAP.find(key), *value);
+        }
+    }
+
     #[test]
     fn test_keys() {
         static MAP: PhfOrderedMap<int> = phf_ordered_map!(
@@ -213,6 +748,80 @@ mod ordered_map {
         let vec = MAP.values().map(|&v| v).collect::<Vec<_>>();
         assert_eq!(vec, vec!(10, 11, 12));
     }
+
+    #[test]
+    fn test_alternation() {
+        static MAP: PhfOrderedMap<int> = phf_ordered_map!(
+            "foo" => 10,
+            "bar" | "baz" => 11,
+            "qux" => 12,
+        );
+        let vec = MAP.entries().map(|(k, &v)| (k, v)).collect::<Vec<_>>();
+        assert_eq!(vec, vec!(("foo", 10), ("bar", 11), ("baz", 11),
+                             ("qux", 12)));
+        assert_eq!(Some("baz"), MAP.find_key(&"baz"));
+        assert_eq!(4, MAP.len());
+    }
+
+    #[test]
+    fn test_on_duplicate() {
+        static FIRST: PhfOrderedMap<int> = phf_ordered_map!(
+            #![on_duplicate = "first"]
+            "foo" => 10,
+            "bar" => 11,
+            "foo" => 12,
+        );
+        let vec = FIRST.entries().map(|(k, &v)| (k, v)).collect::<Vec<_>>();
+        assert_eq!(vec, vec!(("foo", 10), ("bar", 11)));
+
+        static LAST: PhfOrderedMap<int> = phf_ordered_map!(
+            #![on_duplicate = "last"]
+            "foo" => 10,
+            "bar" => 11,
+            "foo" => 12,
+        );
+        let vec = LAST.entries().map(|(k, &v)| (k, v)).collect::<Vec<_>>();
+        assert_eq!(vec, vec!(("bar", 11), ("foo", 12)));
+    }
+
+    #[test]
+    fn test_validate() {
+        static MAP: PhfOrderedMap<int> = phf_ordered_map!(
+            "foo" => 10,
+            "bar" => 11,
+            "baz" => 12,
+        );
+        assert!(Ok(()) == MAP.validate());
+    }
+
+    #[test]
+    fn test_validate_idxs() {
+        static SHORT: PhfOrderedMap<int> = PhfOrderedMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [(0, 0)],
+            idxs: &'static [0],
+            entries: &'static [("foo", 0), ("bar", 1)],
+            values: &'static [10, 11],
+            lens: &'static [3],
+            len_idxs: &'static [0, 1],
+            len_buckets: &'static [(3, 2)],
+        };
+        assert!(Err(IndexCountMismatch(1, 2)) == SHORT.validate());
+
+        static OUT_OF_BOUNDS: PhfOrderedMap<int> = PhfOrderedMap {
+            k1: 0,
+            k2: 0,
+            disps: &'static [(0, 0)],
+            idxs: &'static [0, 2],
+            entries: &'static [("foo", 0), ("bar", 1)],
+            values: &'static [10, 11],
+            lens: &'static [3],
+            len_idxs: &'static [0, 1],
+            len_buckets: &'static [(3, 2)],
+        };
+        assert!(Err(IndexOutOfBounds(1, 2)) == OUT_OF_BOUNDS.validate());
+    }
 }
 
 mod ordered_set {
@@ -242,6 +851,35 @@ mod ordered_set {
         assert_eq!(3, SET.len());
     }
 
+    #[test]
+    fn test_empty() {
+        static EMPTY: PhfOrderedSet = phf_ordered_set! {};
+        static SET: PhfOrderedSet = phf_ordered_set! {
+            "hello",
+        };
+        assert!(!EMPTY.contains(&"hello"));
+        assert_eq!(0, EMPTY.len());
+        assert!(EMPTY.iter().next().is_none());
+        assert!(EMPTY.iter().next_back().is_none());
+        assert!(EMPTY.iter().idx(0).is_none());
+        assert_eq!("{}".to_string(), format!("{}", EMPTY));
+        assert!(EMPTY.is_subset(&SET));
+        assert!(!SET.is_subset(&EMPTY));
+        assert!(EMPTY.is_disjoint(&SET));
+    }
+
+    #[test]
+    fn test_find_longest_prefix() {
+        static SET: PhfOrderedSet = phf_ordered_set! {
+            #![longest_prefix]
+            "-",
+            "--",
+        };
+        assert_eq!(Some("--"), SET.find_longest_prefix("--help"));
+        assert_eq!(Some("-"), SET.find_longest_prefix("-h"));
+        assert_eq!(None, SET.find_longest_prefix("help"));
+    }
+
     #[test]
     fn test_iter() {
         static SET: PhfOrderedSet = phf_ordered_set! {
@@ -252,4 +890,1113 @@ mod ordered_set {
         let vec = SET.iter().collect::<Vec<_>>();
         assert_eq!(vec, vec!("hello", "there", "world"));
     }
+
+    #[test]
+    fn test_index_of() {
+        static SET: PhfOrderedSet = phf_ordered_set! {
+            "hello",
+            "there",
+            "world",
+        };
+        assert_eq!(Some(0), SET.index_of(&"hello"));
+        assert_eq!(Some(2), SET.index_of(&"world"));
+        assert_eq!(None, SET.index_of(&"foo"));
+        assert_eq!(Some("there"), SET.key_at(1));
+        assert_eq!(Some("world"), SET.key_at(2));
+        assert_eq!(None, SET.key_at(3));
+    }
+
+    #[test]
+    fn test_suggest() {
+        static SET: PhfOrderedSet = phf_ordered_set! {
+            "hello",
+            "help",
+            "world",
+            "hell",
+        };
+        assert_eq!(vec!["hell", "hello", "help"], SET.suggest("helo", 1));
+        assert_eq!(vec!["world"], SET.suggest("wrold", 1));
+        assert!(SET.suggest("hello world", 2).is_empty());
+    }
+}
+
+mod bimap {
+    use phf::{PhfBiMap, PhfMap, IndexCountMismatch};
+
+    #[test]
+    fn test_two() {
+        static MAP: PhfBiMap<u32> = phf_bimap! {
+            "foo" => 10,
+            "bar" => 11,
+        };
+        assert!(Some(&10) == MAP.find(&"foo"));
+        assert!(Some(&11) == MAP.find(&"bar"));
+        assert_eq!(None, MAP.find(&"asdf"));
+        assert_eq!(Some("foo"), MAP.find_by_value(&10));
+        assert_eq!(Some("bar"), MAP.find_by_value(&11));
+        assert_eq!(None, MAP.find_by_value(&12));
+        assert_eq!(2, MAP.len());
+        assert!(Ok(()) == MAP.validate());
+    }
+
+    #[test]
+    fn test_values() {
+        static NEGATIVE: PhfBiMap<i8> = phf_bimap! {
+            "minus one" => -1,
+            "one" => 1,
+        };
+        static STRS: PhfBiMap<&'static str> = phf_bimap! {
+            "foo" => "oof",
+            "bar" => "rab",
+        };
+        static CHARS: PhfBiMap<char> = phf_bimap! {
+            "a" => 'a',
+            "b" => 'b',
+        };
+        assert_eq!(Some("minus one"), NEGATIVE.find_by_value(&-1));
+        assert_eq!(Some("bar"), STRS.find_by_value(&"rab"));
+        assert!(CHARS.contains_value(&'b'));
+        assert!(!CHARS.contains_value(&'c'));
+    }
+
+    #[test]
+    fn test_empty() {
+        static EMPTY: PhfBiMap<u32> = phf_bimap! {};
+        assert_eq!(None, EMPTY.find_by_value(&0));
+        assert_eq!(0, EMPTY.len());
+        assert!(Ok(()) == EMPTY.validate());
+    }
+
+    #[test]
+    fn test_validate() {
+        static MAP: PhfBiMap<u32> = PhfBiMap {
+            map: PhfMap {
+                k1: 0,
+                k2: 0,
+                disps: &[(0, 0)],
+                entries: &[("foo", 0)],
+                values: &[10],
+                lens: &[3],
+                len_idxs: &[0],
+                len_buckets: &[(3, 1)],
+            },
+            k1: 0,
+            k2: 0,
+            disps: &[(0, 0)],
+            idxs: &[0],
+        };
+        assert!(Ok(()) == MAP.validate());
+        MAP.debug_validate();
+    }
+
+    #[test]
+    fn test_validate_idxs() {
+        static MAP: PhfBiMap<u32> = PhfBiMap {
+            map: PhfMap {
+                k1: 0,
+                k2: 0,
+                disps: &[(0, 0)],
+                entries: &[("foo", 0)],
+                values: &[10],
+                lens: &[3],
+                len_idxs: &[0],
+                len_buckets: &[(3, 1)],
+            },
+            k1: 0,
+            k2: 0,
+            disps: &[(0, 0)],
+            idxs: &[0, 0],
+        };
+        assert!(Err(IndexCountMismatch(2, 1)) == MAP.validate());
+    }
+}
+
+mod sorted_map {
+    use phf::{PhfSortedMap, PhfSortedSet, PhfOrderedMap, OutOfOrder};
+    use phf_codegen::generate_hash;
+    use super::leak;
+
+    static MAP: PhfSortedMap<int> = phf_sorted_map! {
+        "push" => 3,
+        "pull" => 2,
+        "commit" => 0,
+        "prune" => 1,
+        "pu" => 4,
+    };
+
+    fn keys<'a, I: Iterator<(&'static str, &'a int)>>(iter: I)
+            -> Vec<&'static str> {
+        iter.map(|(k, _)| k).collect()
+    }
+
+    #[test]
+    fn test_find() {
+        assert!(Some(&3) == MAP.find(&"push"));
+        assert!(Some(&0) == MAP.find(&"commit"));
+        assert_eq!(None, MAP.find(&"fetch"));
+        assert_eq!(5, MAP.len());
+        assert!(Ok(()) == MAP.validate());
+    }
+
+    #[test]
+    fn test_find_many() {
+        let keys = ["push", "fetch", "pu", "commit", "p"];
+        let mut values = [None, ..5];
+        MAP.find_many(keys.as_slice(), values.as_mut_slice());
+        for (key, value) in keys.iter().zip(values.iter()) {
+            assert_eq!(MAP.find(key), *value);
+        }
+    }
+
+    #[test]
+    fn test_order() {
+        assert_eq!(vec!["commit", "prune", "pu", "pull", "push"],
+                   keys(MAP.entries()));
+    }
+
+    #[test]
+    fn test_range() {
+        assert_eq!(vec!["prune", "pu"], keys(MAP.range("p", "pul")));
+        assert_eq!(vec!["commit"], keys(MAP.range("", "d")));
+        assert_eq!(Vec::<&str>::new(), keys(MAP.range("q", "a")));
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(vec!["pu", "pull", "push"], keys(MAP.prefix("pu")));
+        assert_eq!(vec!["pull"], keys(MAP.prefix("pul")));
+        assert_eq!(5, keys(MAP.prefix("")).len());
+        assert_eq!(Vec::<&str>::new(), keys(MAP.prefix("x")));
+    }
+
+    #[test]
+    fn test_set() {
+        static SET: PhfSortedSet = phf_sorted_set! {
+            "c",
+            "a",
+            "b",
+        };
+        assert_eq!(vec!["a", "b", "c"], SET.iter().collect::<Vec<_>>());
+        assert_eq!(vec!["b"], SET.range("b", "c").collect::<Vec<_>>());
+        assert!(SET.contains(&"a"));
+        assert_eq!(Some(0), SET.index_of(&"a"));
+        assert_eq!(Some(2), SET.index_of(&"c"));
+        assert_eq!(None, SET.index_of(&"d"));
+        assert_eq!(Some("b"), SET.key_at(1));
+        assert_eq!(Some("c"), SET.key_at(2));
+        assert_eq!(None, SET.key_at(3));
+    }
+
+    #[test]
+    fn test_validate_out_of_order() {
+        let keys = ["a", "b", "c"];
+        let state = generate_hash(keys.as_slice());
+
+        // Swap the first two entries, and the slots referring to them, so that
+        // every key is still found but the keys are no longer sorted
+        let mut entries = keys.iter().map(|&key| (key, 0u)).collect::<Vec<_>>();
+        entries.as_mut_slice().swap(0, 1);
+        let idxs = state.map.iter().map(|&idx| {
+            match idx {
+                0 => 1,
+                1 => 0,
+                idx => idx,
+            }
+        }).collect();
+
+        let map = PhfSortedMap {
+            map: PhfOrderedMap {
+                k1: state.k1,
+                k2: state.k2,
+                disps: leak(state.disps.clone()),
+                idxs: leak(idxs),
+                entries: leak(entries),
+                values: leak(vec![()]),
+                lens: leak(vec![1u]),
+                len_idxs: leak(vec![0u, 1, 2]),
+                len_buckets: leak(vec![(1u, 3u)]),
+            },
+        };
+        assert!(Ok(()) == map.map.validate());
+        assert!(Err(OutOfOrder("b", "a")) == map.validate());
+    }
+}
+
+mod search {
+    use phf::{PhfSearchMap, PhfSearchSet, LeftmostLongest, WholeWords};
+
+    static MAP: PhfSearchMap<int> = phf_search_map! {
+        "he" => 1,
+        "she" => 2,
+        "his" => 3,
+        "hers" => 4,
+    };
+
+    #[test]
+    fn test_find() {
+        assert!(Some(&2) == MAP.find(&"she"));
+        assert_eq!(None, MAP.find(&"her"));
+        assert_eq!(4, MAP.len());
+    }
+
+    #[test]
+    fn test_find_many() {
+        let keys = ["she", "her", "hers", "he"];
+        let mut values = [None, ..4];
+        MAP.find_many(keys.as_slice(), values.as_mut_slice());
+        for (key, value) in keys.iter().zip(values.iter()) {
+            assert_eq!(MAP.find(key), *value);
+        }
+    }
+
+    #[test]
+    fn test_leftmost_longest() {
+        let found = MAP.search("ushers his", LeftmostLongest)
+                       .map(|(offset, key, &value)| (offset, key, value))
+                       .collect::<Vec<_>>();
+        assert_eq!(vec![(1, "she", 2), (7, "his", 3)], found);
+
+        static SET: PhfSearchSet = phf_search_set! {
+            "bcd",
+            "abcde",
+            "cd",
+        };
+        let found = SET.search_bytes("xabcdey cd".as_bytes(), LeftmostLongest)
+                       .collect::<Vec<_>>();
+        assert_eq!(vec![(1, "abcde"), (8, "cd")], found);
+    }
+
+    #[test]
+    fn test_whole_words() {
+        static SET: PhfSearchSet = phf_search_set! {
+            "foo",
+            "foobar",
+        };
+        let found = SET.search("foob foobar foo_ foo", WholeWords)
+                       .collect::<Vec<_>>();
+        assert_eq!(vec![(5, "foobar"), (17, "foo")], found);
+        let found = SET.search("foobarfoo", LeftmostLongest)
+                       .collect::<Vec<_>>();
+        assert_eq!(vec![(0, "foobar"), (6, "foo")], found);
+    }
+
+    #[test]
+    fn test_empty() {
+        static EMPTY: PhfSearchSet = phf_search_set! {};
+        assert!(EMPTY.search("foo", LeftmostLongest).next().is_none());
+        assert!(MAP.search("", LeftmostLongest).next().is_none());
+    }
+
+    #[test]
+    fn test_index_of() {
+        static SET: PhfSearchSet = phf_search_set! {
+            "he",
+            "she",
+        };
+        for &value in ["he", "she"].iter() {
+            let idx = SET.index_of(&value).unwrap();
+            assert_eq!(Some(value), SET.key_at(idx));
+        }
+        assert_eq!(None, SET.index_of(&"his"));
+        assert_eq!(None, SET.key_at(2));
+    }
+}
+
+mod multimap {
+    use phf::PhfMultiMap;
+
+    static MAP: PhfMultiMap<int> = phf_multimap! {
+        "foo" => 10,
+        "bar" => 11,
+        "foo" => 12,
+        "foo" | "baz" => 13,
+    };
+
+    #[test]
+    fn test_find() {
+        let foo = MAP.find(&"foo").unwrap();
+        assert_eq!(vec![10, 12, 13], Vec::from_slice(foo));
+        assert_eq!(vec![11], Vec::from_slice(MAP.find(&"bar").unwrap()));
+        assert_eq!(vec![13], Vec::from_slice(MAP.find(&"baz").unwrap()));
+        assert!(MAP.find(&"asdf").is_none());
+        assert_eq!(3, MAP.len());
+        assert!(Ok(()) == MAP.validate());
+    }
+
+    #[test]
+    fn test_find_many() {
+        let keys = ["foo", "asdf", "baz", "bar"];
+        let mut values = [None, ..4];
+        MAP.find_many(keys.as_slice(), values.as_mut_slice());
+        for (key, value) in keys.iter().zip(values.iter()) {
+            assert!(MAP.find(key) == *value);
+        }
+    }
+
+    #[test]
+    fn test_entries() {
+        let mut entries = MAP.entries().map(|(k, &v)| (k, v))
+                             .collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(vec![("bar", 11), ("baz", 13), ("foo", 10), ("foo", 12),
+                        ("foo", 13)],
+                   entries);
+
+        let mut keys = MAP.keys().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(vec!["bar", "baz", "foo"], keys);
+    }
+
+    #[test]
+    fn test_index_of() {
+        let idx = MAP.index_of(&"foo").unwrap();
+        assert_eq!(Some("foo"), MAP.key_at(idx));
+        let (key, values) = MAP.entry_at(idx).unwrap();
+        assert_eq!("foo", key);
+        assert_eq!(vec![10, 12, 13], Vec::from_slice(values));
+        assert_eq!(None, MAP.index_of(&"qux"));
+    }
+
+    #[test]
+    fn test_empty() {
+        static EMPTY: PhfMultiMap<int> = phf_multimap! {};
+        assert!(EMPTY.find(&"foo").is_none());
+        assert!(EMPTY.entries().next().is_none());
+        assert_eq!(0, EMPTY.len());
+    }
+}
+
+mod nested {
+    use phf::{PhfMap, PhfSet};
+
+    #[test]
+    fn test_map_of_maps() {
+        static MAP: PhfMap<&'static PhfMap<int>> = phf_map! {
+            "foo" => phf_map! {
+                "bar" => 10,
+                "baz" => 11,
+            },
+            "qux" => phf_map! {},
+        };
+        assert_eq!(Some(&10), MAP.find(&"foo").unwrap().find(&"bar"));
+        assert_eq!(Some(&11), MAP.find_path(&"foo", &"baz"));
+        assert_eq!(None, MAP.find_path(&"qux", &"bar"));
+        assert_eq!(None, MAP.find_path(&"asdf", &"bar"));
+        assert_eq!(None, MAP.find_path(&"foo", &"qux"));
+    }
+
+    #[test]
+    fn test_map_of_sets() {
+        static MAP: PhfMap<&'static PhfSet> = phf_map! {
+            "foo" => phf_set! { "bar", "baz" },
+        };
+        assert!(MAP.contains_path(&"foo", &"bar"));
+        assert!(!MAP.contains_path(&"foo", &"qux"));
+        assert!(!MAP.contains_path(&"bar", &"baz"));
+    }
+}
+
+mod keys {
+    use phf::{PhfMap, PhfSet};
+
+    #[phf_key]
+    static FOO: &'static str = "foo";
+
+    #[phf_key]
+    static BAR: &'static str = concat!("b", "ar");
+
+    #[phf_key]
+    static QUOTED: &'static str = "\"quoted\"";
+
+    #[test]
+    fn test_map() {
+        static MAP: PhfMap<int> = phf_map!(
+            FOO => 10,
+            concat!(BAR, "baz") => 11,
+            concat!(FOO, "-", QUOTED) => 12,
+        );
+        assert!(Some(&10) == MAP.find(&FOO));
+        assert!(Some(&11) == MAP.find(&"barbaz"));
+        assert!(Some(&12) == MAP.find(&"foo-\"quoted\""));
+        assert_eq!(3, MAP.len());
+    }
+
+    #[test]
+    fn test_set() {
+        static SET: PhfSet = phf_set! {
+            FOO,
+            self::BAR,
+        };
+        assert!(SET.contains(&"foo"));
+        assert!(SET.contains(&"bar"));
+    }
+
+    mod child {
+        use phf::PhfSet;
+
+        #[phf_key]
+        static FOO: &'static str = "child foo";
+
+        #[test]
+        fn test_paths() {
+            static SET: PhfSet = phf_set! {
+                FOO,
+                super::FOO,
+                ::keys::BAR,
+                concat!(FOO, "!"),
+            };
+            assert!(SET.contains(&"child foo"));
+            assert!(SET.contains(&"foo"));
+            assert!(SET.contains(&"bar"));
+            assert!(SET.contains(&"child foo!"));
+        }
+    }
+}
+
+mod items {
+    phf_map! {
+        /// A documented map.
+        pub static MAP: int = {
+            "foo" => 10,
+            "bar" => 11,
+        };
+
+        static OTHER_MAP: &'static str = {
+            "baz" => "qux",
+        };
+    }
+
+    phf_set! {
+        #[allow(dead_code)]
+        static SET = {
+            "hello",
+            "world",
+        };
+    }
+
+    phf_ordered_map! {
+        static ORDERED_MAP: int = {
+            "foo" => 10,
+            "bar" => 11,
+        };
+    }
+
+    phf_bimap! {
+        static BIMAP: u32 = {
+            "foo" => 10,
+        };
+    }
+
+    phf_ordered_set! {
+        static ORDERED_SET = {
+            "hello",
+            "world"
+        };
+    }
+
+    #[test]
+    fn test_map() {
+        assert!(Some(&10) == MAP.find(&"foo"));
+        assert!(Some(&11) == MAP.find(&"bar"));
+        assert_eq!(2, MAP.len());
+        assert!(Some(&"qux") == OTHER_MAP.find(&"baz"));
+    }
+
+    #[test]
+    fn test_set() {
+        assert!(SET.contains(&"hello"));
+        assert!(SET.contains(&"world"));
+        assert_eq!(2, SET.len());
+    }
+
+    #[test]
+    fn test_ordered_map() {
+        let vec = ORDERED_MAP.entries().map(|(k, &v)| (k, v))
+                             .collect::<Vec<_>>();
+        assert_eq!(vec, vec!(("foo", 10), ("bar", 11)));
+    }
+
+    #[test]
+    fn test_ordered_set() {
+        let vec = ORDERED_SET.iter().collect::<Vec<_>>();
+        assert_eq!(vec, vec!("hello", "world"));
+    }
+
+    #[test]
+    fn test_get() {
+        assert_eq!(&10, phf_get!(MAP, "foo"));
+        assert_eq!(&11, phf_get!(self::MAP, concat!("b", "ar")));
+        assert_eq!(&"qux", phf_get!(OTHER_MAP, "baz"));
+        assert_eq!(&11, phf_get!(ORDERED_MAP, "bar"));
+        assert_eq!(&10, phf_get!(BIMAP, "foo"));
+    }
+
+    mod child {
+        #[test]
+        fn test_get() {
+            assert_eq!(&10, phf_get!(super::MAP, "foo"));
+            assert_eq!(&"qux", phf_get!(::items::OTHER_MAP, "baz"));
+        }
+    }
+}
+
+mod match_ {
+    fn classify(s: &str) -> &'static str {
+        phf_match!(s,
+            "loop" | "while" => "loop",
+            "if" => {
+                "branch"
+            }
+            #[cfg(phf_test_cfg)]
+            "match" => "branch",
+            #[cfg(not(phf_test_cfg))]
+            "match" => "match",
+            _ => "other",
+        )
+    }
+
+    #[test]
+    fn test_arms() {
+        assert_eq!("loop", classify("loop"));
+        assert_eq!("loop", classify("while"));
+        assert_eq!("branch", classify("if"));
+        assert_eq!("match", classify("match"));
+        assert_eq!("other", classify("fn"));
+        assert_eq!("other", classify(""));
+    }
+
+    #[test]
+    fn test_only_fallback() {
+        let s = "foo".to_string();
+        assert_eq!(1, phf_match!(s.as_slice(), _ => 1));
+    }
+}
+
+mod enum_ {
+    phf_enum! {
+        #[deriving(PartialEq, Show)]
+        enum Keyword {
+            /// A documented variant.
+            Loop = "loop",
+            While = concat!("wh", "ile"),
+            #[cfg(phf_test_cfg)]
+            Match = "match",
+            Fn = "fn",
+        }
+    }
+
+    #[test]
+    fn test_map() {
+        assert_eq!(Some(&Loop), Keyword::map().find(&"loop"));
+        assert_eq!(Some(&While), Keyword::map().find(&"while"));
+        assert_eq!(None, Keyword::map().find(&"match"));
+        assert_eq!(3, Keyword::map().len());
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!(Some(Fn), from_str::<Keyword>("fn"));
+        assert_eq!(None, from_str::<Keyword>("loo"));
+    }
+
+    #[test]
+    fn test_as_str() {
+        for &keyword in [Loop, While, Fn].iter() {
+            let s = keyword.as_str();
+            let key = Keyword::map().find_key(&s).unwrap();
+            assert_eq!(key.as_ptr(), s.as_ptr());
+            assert_eq!(Some(keyword), from_str(s));
+        }
+    }
+}
+
+mod table {
+    use phf::{PhfSet, PhfTable};
+
+    static KEYS: PhfSet = phf_set! {
+        "a",
+        "bc",
+        "def",
+    };
+
+    #[test]
+    fn test_find() {
+        let mut table = PhfTable::new(&KEYS, |key| key.len());
+        assert_eq!(Some(&1), table.find(&"a"));
+        assert_eq!(Some(&2), table.find(&"bc"));
+        assert_eq!(Some(&3), table.find(&"def"));
+        assert_eq!(None, table.find(&"qux"));
+        *table.find_mut(&"bc").unwrap() = 10;
+        assert_eq!(Some(&10), table.find(&"bc"));
+        assert_eq!(Some(&1), table.find(&"a"));
+        assert_eq!(Some(&3), table.find(&"def"));
+        assert!(table.find_mut(&"qux").is_none());
+        assert!(table.contains_key(&"def"));
+        assert_eq!(3, table.len());
+    }
+
+    #[test]
+    fn test_insert_existing() {
+        let mut table = PhfTable::with_value(&KEYS, 0);
+        assert_eq!(Ok(0), table.insert_existing(&"a", 5));
+        assert_eq!(Ok(5), table.insert_existing(&"a", 6));
+        assert_eq!(Err(7), table.insert_existing(&"qux", 7));
+        assert_eq!(Some(&6), table.find(&"a"));
+        assert_eq!(Some(&0), table.find(&"bc"));
+        assert_eq!(3, table.len());
+    }
+
+    #[test]
+    fn test_entries() {
+        let mut table = PhfTable::with_value(&KEYS, 1);
+        for (key, value) in table.mut_entries() {
+            *value += key.len();
+        }
+        let mut entries = table.entries().map(|(k, &v)| (k, v))
+                               .collect::<Vec<_>>();
+        entries.sort();
+        assert_eq!(vec![("a", 2), ("bc", 3), ("def", 4)], entries);
+    }
+}
+
+mod columns {
+    phf_table! {
+        static TABLE: Table {
+            name: &'static str,
+            flags: uint,
+        } = {
+            "foo" | "fu" => ("Foo", 1),
+            "bar" => ("Bar", 2),
+            #[cfg(not(foo))]
+            "baz" => ("Baz", 3),
+            #[cfg(foo)]
+            "qux" => ("Qux", 4),
+        };
+
+        static SINGLE: Single {
+            value: int,
+        } = {
+            "foo" => 10,
+            "bar" => (11,),
+        };
+
+        static DEDUPED: Deduped {
+            name: &'static str,
+            flags: uint,
+        } = {
+            #![on_duplicate = "last"]
+            "foo" => ("Foo", 1),
+            "bar" => ("Bar", 2),
+            "foo" => ("Fu", 3),
+        };
+    }
+
+    #[test]
+    fn test_columns() {
+        for &(key, name, flags) in [("foo", "Foo", 1u), ("fu", "Foo", 1),
+                                    ("bar", "Bar", 2), ("baz", "Baz", 3)]
+                                   .iter() {
+            let idx = TABLE.index_of(&key).unwrap();
+            assert_eq!(name, TABLE.name()[idx]);
+            assert_eq!(flags, TABLE.flags()[idx]);
+        }
+        assert_eq!(None, TABLE.index_of(&"qux"));
+        assert_eq!(Some(&"Bar"), TABLE.find_name(&"bar"));
+        assert_eq!(Some(&3), TABLE.find_flags(&"baz"));
+        assert_eq!(None, TABLE.find_flags(&"qux"));
+        assert_eq!(4, TABLE.keys().len());
+        assert_eq!(4, TABLE.name().len());
+    }
+
+    #[test]
+    fn test_single() {
+        let idx = SINGLE.index_of(&"bar").unwrap();
+        assert_eq!(11, SINGLE.value()[idx]);
+        assert_eq!(Some(&10), SINGLE.find_value(&"foo"));
+        assert!(Ok(()) == SINGLE.keys().validate());
+    }
+
+    #[test]
+    fn test_on_duplicate() {
+        let idx = DEDUPED.index_of(&"foo").unwrap();
+        assert_eq!("Fu", DEDUPED.name()[idx]);
+        assert_eq!(3, DEDUPED.flags()[idx]);
+        assert_eq!(Some(&"Fu"), DEDUPED.find_name(&"foo"));
+        assert_eq!(2, DEDUPED.name().len());
+    }
+}
+
+mod prehashed {
+    use phf::{PhfMap, PhfSet, PhfOrderedMap, PhfKey, PhfBiMap, PhfMultiMap,
+              PhfSortedMap, PhfSortedSet, PhfSearchMap, PhfSearchSet};
+
+    static MAP: PhfMap<int> = phf_map! {
+        #![seed = 42]
+        "foo" => 10,
+        "bar" => 11,
+        "baz" => 12,
+    };
+
+    static SET: PhfSet = phf_set! {
+        #![seed = 42]
+        "foo",
+        "qux",
+    };
+
+    static ORDERED_MAP: PhfOrderedMap<int> = phf_ordered_map! {
+        #![seed = 42]
+        #![on_duplicate = "first"]
+        "foo" => 20,
+        "bar" => 21,
+        "foo" => 22,
+    };
+
+    static UNSEEDED: PhfMap<int> = phf_map! {
+        "foo" => 30,
+    };
+
+    static BIMAP: PhfBiMap<int> = phf_bimap! {
+        #![seed = 42]
+        "foo" => 40,
+        "bar" => 41,
+    };
+
+    static MULTIMAP: PhfMultiMap<int> = phf_multimap! {
+        #![seed = 42]
+        "foo" => 50,
+        "foo" => 51,
+    };
+
+    static SORTED_SET: PhfSortedSet = phf_sorted_set! {
+        #![seed = 42]
+        "qux",
+        "foo",
+    };
+
+    static SEARCH_MAP: PhfSearchMap<int> = phf_search_map! {
+        #![seed = 42]
+        "foo" => 60,
+    };
+
+    static SEARCH_SET: PhfSearchSet = phf_search_set! {
+        #![seed = 42]
+        "qux",
+    };
+
+    phf_table! {
+        static TABLE: Table {
+            value: int,
+        } = {
+            #![seed = 42]
+            "bar" => 70,
+            "foo" => 71,
+        };
+    }
+
+    #[test]
+    fn test_find_prehashed() {
+        let foo = PhfKey::new("foo", 42);
+        let bar = PhfKey::new("bar", 42);
+        let qux = PhfKey::new("qux", 42);
+        assert_eq!("foo", foo.as_slice());
+        assert_eq!(Some(&10), MAP.find_prehashed(&foo));
+        assert_eq!(Some(&11), MAP.find_prehashed(&bar));
+        assert_eq!(None, MAP.find_prehashed(&qux));
+        assert!(SET.contains_prehashed(&foo));
+        assert!(SET.contains_prehashed(&qux));
+        assert!(!SET.contains_prehashed(&bar));
+        assert_eq!(Some(&20), ORDERED_MAP.find_prehashed(&foo));
+        assert_eq!(None, ORDERED_MAP.find_prehashed(&qux));
+        assert!(Ok(()) == MAP.validate());
+        assert!(Ok(()) == ORDERED_MAP.validate());
+    }
+
+    #[test]
+    fn test_other_kinds() {
+        let foo = PhfKey::new("foo", 42);
+        let qux = PhfKey::new("qux", 42);
+        assert_eq!(Some(&40), BIMAP.find_prehashed(&foo));
+        assert_eq!(BIMAP.index_of(&"foo"), BIMAP.index_of_prehashed(&foo));
+        assert_eq!(None, BIMAP.find_prehashed(&qux));
+        let values = MULTIMAP.find_prehashed(&foo).unwrap();
+        assert_eq!(vec![50, 51], Vec::from_slice(values));
+        assert_eq!(None, MULTIMAP.index_of_prehashed(&qux));
+        assert!(SORTED_SET.contains_prehashed(&qux));
+        assert_eq!(Some(0), SORTED_SET.index_of_prehashed(&foo));
+        assert_eq!(Some(&60), SEARCH_MAP.find_prehashed(&foo));
+        assert!(SEARCH_SET.contains_prehashed(&qux));
+        assert!(!SEARCH_SET.contains_prehashed(&foo));
+        assert_eq!(SET.index_of(&"foo"), SET.index_of_prehashed(&foo));
+        let idx = TABLE.index_of_prehashed(&foo).unwrap();
+        assert_eq!(71, TABLE.value()[idx]);
+    }
+
+    #[test]
+    fn test_other_seed() {
+        let foo = PhfKey::new("foo", 7);
+        assert_eq!(Some(&10), MAP.find_prehashed(&foo));
+        assert_eq!(Some(&30), UNSEEDED.find_prehashed(&foo));
+        assert_eq!(None, UNSEEDED.find_prehashed(&PhfKey::new("bar", 42)));
+    }
+}
+
+mod atom {
+    use std::collections::HashSet;
+    use phf::atom::Atom;
+
+    phf_set! {
+        static ATOMS = {
+            "div",
+            "span",
+        };
+    }
+
+    struct TestAtoms;
+
+    phf_atom_set!(TestAtoms, ATOMS)
+
+    type TestAtom = Atom<TestAtoms>;
+
+    static DIV: TestAtom = phf_atom!(TestAtoms, ATOMS, "div");
+
+    #[test]
+    fn test_static() {
+        let div: TestAtom = Atom::from_slice("div");
+        assert!(div.is_static());
+        assert!(div == DIV);
+        assert!(div == phf_atom!(TestAtoms, ATOMS, "div"));
+        assert!(div != phf_atom!(TestAtoms, ATOMS, "span"));
+        assert_eq!("div", div.as_slice());
+        assert_eq!("span".to_string(),
+                   format!("{}", phf_atom!(TestAtoms, ATOMS, "span")));
+    }
+
+    #[test]
+    fn test_dynamic() {
+        let a: TestAtom = Atom::from_slice("test_dynamic");
+        let b: TestAtom = Atom::from_slice("test_dynamic");
+        let c: TestAtom = Atom::from_slice("test_dynamic_other");
+        assert!(!a.is_static());
+        assert!(a == b);
+        assert!(a != c);
+        assert_eq!("test_dynamic", b.as_slice());
+
+        let d = a.clone();
+        drop(a);
+        drop(b);
+        assert_eq!("test_dynamic", d.as_slice());
+        drop(d);
+
+        let e: TestAtom = Atom::from_slice("test_dynamic");
+        assert_eq!("test_dynamic", e.as_slice());
+    }
+
+    #[test]
+    fn test_hash() {
+        let mut set = HashSet::new();
+        set.insert(Atom::from_slice("div") as TestAtom);
+        set.insert(Atom::from_slice("test_hash"));
+        assert!(set.contains(&phf_atom!(TestAtoms, ATOMS, "div")));
+        assert!(set.contains(&Atom::from_slice("test_hash")));
+        assert!(!set.contains(&Atom::from_slice("span")));
+    }
+}
+
+mod bench {
+    use test::Bencher;
+    use phf::PhfMap;
+    use phf_codegen::generate_hash;
+    use super::leak;
+
+    static ELEMENTS: PhfMap<uint> = phf_map! {
+        "a" => 0, "abbr" => 1, "address" => 2, "area" => 3, "article" => 4,
+        "aside" => 5, "audio" => 6, "b" => 7, "base" => 8, "bdi" => 9,
+        "bdo" => 10, "blockquote" => 11, "body" => 12, "br" => 13,
+        "button" => 14, "canvas" => 15, "caption" => 16, "cite" => 17,
+        "code" => 18, "col" => 19, "colgroup" => 20, "data" => 21,
+        "datalist" => 22, "dd" => 23, "del" => 24, "details" => 25,
+        "dfn" => 26, "dialog" => 27, "div" => 28, "dl" => 29, "dt" => 30,
+        "em" => 31, "embed" => 32, "fieldset" => 33, "figcaption" => 34,
+        "figure" => 35, "footer" => 36, "form" => 37, "h1" => 38, "h2" => 39,
+        "h3" => 40, "h4" => 41, "h5" => 42, "h6" => 43, "head" => 44,
+        "header" => 45, "hgroup" => 46, "hr" => 47, "html" => 48, "i" => 49,
+        "iframe" => 50, "img" => 51, "input" => 52, "ins" => 53, "kbd" => 54,
+        "keygen" => 55, "label" => 56, "legend" => 57, "li" => 58,
+        "link" => 59, "main" => 60, "map" => 61, "mark" => 62, "menu" => 63,
+        "menuitem" => 64, "meta" => 65, "meter" => 66, "nav" => 67,
+        "noscript" => 68, "object" => 69, "ol" => 70, "optgroup" => 71,
+        "option" => 72, "output" => 73, "p" => 74, "param" => 75, "pre" => 76,
+        "progress" => 77, "q" => 78, "rp" => 79, "rt" => 80, "ruby" => 81,
+        "s" => 82, "samp" => 83, "script" => 84, "section" => 85,
+        "select" => 86, "small" => 87, "source" => 88, "span" => 89,
+        "strong" => 90, "style" => 91, "sub" => 92, "summary" => 93,
+        "sup" => 94, "table" => 95, "tbody" => 96, "td" => 97,
+        "template" => 98, "textarea" => 99, "tfoot" => 100, "th" => 101,
+        "thead" => 102, "time" => 103, "title" => 104, "tr" => 105,
+        "track" => 106, "u" => 107, "ul" => 108, "var" => 109, "video" => 110,
+        "wbr" => 111,
+    };
+
+    static PROBES: uint = 4096;
+
+    // The number of keys of `large_map`
+    static LARGE_LEN: uint = 50000;
+
+    // Keys of `map` in a scrambled order, with every fourth probe a miss
+    fn probes(map: &PhfMap<uint>) -> Vec<&'static str> {
+        let keys = map.keys().collect::<Vec<_>>();
+        Vec::from_fn(PROBES, |i| {
+            if i % 4 == 3 {
+                "custom-element"
+            } else {
+                *keys.get(i * 7919 % keys.len())
+            }
+        })
+    }
+
+    // A map too large for its tables to stay in the cache, built at run time
+    fn large_map() -> PhfMap<uint> {
+        let names = leak(Vec::from_fn(LARGE_LEN, |i| format!("key{}", i)));
+        let keys = names.iter().map(|name| name.as_slice())
+                        .collect::<Vec<&'static str>>();
+        let state = generate_hash(keys.as_slice());
+        let entries = state.map.iter().map(|&idx| (*keys.get(idx), idx))
+                                      .collect();
+
+        // The names get longer as they are numbered, so listing the entry of
+        // each name in turn orders the entries by the length of their keys
+        let mut len_idxs = Vec::from_elem(LARGE_LEN, 0u);
+        for (i, &idx) in state.map.iter().enumerate() {
+            *len_idxs.get_mut(idx) = i;
+        }
+
+        PhfMap {
+            k1: state.k1,
+            k2: state.k2,
+            disps: leak(state.disps.clone()),
+            entries: leak(entries),
+            values: leak(Vec::from_fn(LARGE_LEN, |i| i)),
+            lens: leak(vec![]),
+            len_idxs: leak(len_idxs),
+            len_buckets: leak(vec![(4u, 10u), (5, 100), (6, 1000), (7, 10000),
+                                   (8, LARGE_LEN)]),
+        }
+    }
+
+    fn bench_find_in(b: &mut Bencher, map: &PhfMap<uint>) {
+        let keys = probes(map);
+        b.iter(|| {
+            keys.iter().filter(|key| map.find(*key).is_some()).count()
+        });
+    }
+
+    fn bench_find_many_in(b: &mut Bencher, map: &PhfMap<uint>) {
+        let keys = probes(map);
+        let mut values = Vec::from_elem(keys.len(), None);
+        b.iter(|| {
+            map.find_many(keys.as_slice(), values.as_mut_slice());
+            values.iter().filter(|value| value.is_some()).count()
+        });
+    }
+
+    #[bench]
+    fn bench_find(b: &mut Bencher) {
+        bench_find_in(b, &ELEMENTS)
+    }
+
+    #[bench]
+    fn bench_find_many(b: &mut Bencher) {
+        bench_find_many_in(b, &ELEMENTS)
+    }
+
+    #[bench]
+    fn bench_find_large(b: &mut Bencher) {
+        bench_find_in(b, &large_map())
+    }
+
+    #[bench]
+    fn bench_find_many_large(b: &mut Bencher) {
+        bench_find_many_in(b, &large_map())
+    }
+}
+
+mod codegen {
+    use std::io::MemWriter;
+    use std::str;
+    use phf;
+    use phf_codegen::{generate_hash, generate_hash_with_seed, PhfMapBuilder,
+                      PhfOrderedSetBuilder, PhfSetBuilder};
+
+    #[test]
+    fn test_generate_hash() {
+        let keys = ["foo", "bar", "baz", "qux", "a", "b", "c"];
+        let state = generate_hash(keys.as_slice());
+        for (i, &key) in keys.iter().enumerate() {
+            let (g, f1, f2) = phf::hash(key, state.k1, state.k2);
+            let (d1, d2) = *state.disps.get(g % state.disps.len());
+            let idx = phf::displace(f1, f2, d1, d2) % state.map.len();
+            assert_eq!(i, *state.map.get(idx));
+        }
+    }
+
+    #[test]
+    fn test_generate_hash_with_seed() {
+        let keys = ["foo", "bar", "baz", "qux", "a", "b", "c"];
+        let state = generate_hash_with_seed(keys.as_slice(), 1234).unwrap();
+        assert_eq!((state.k1, state.k2), phf::seed_keys(1234));
+        for (i, &key) in keys.iter().enumerate() {
+            let (g, f1, f2) = phf::hash(key, state.k1, state.k2);
+            let (d1, d2) = *state.disps.get(g % state.disps.len());
+            let idx = phf::displace(f1, f2, d1, d2) % state.map.len();
+            assert_eq!(i, *state.map.get(idx));
+        }
+    }
+
+    #[test]
+    fn test_map() {
+        let mut w = MemWriter::new();
+        PhfMapBuilder::new().entry("foo", "10").build(&mut w).unwrap();
+        let out = str::from_utf8(w.get_ref()).unwrap();
+        assert!(out.starts_with("::phf::PhfMap {"));
+        assert!(out.contains("(\"foo\", 0),"));
+        assert!(out.contains("values: &'static [\n        10,\n    ],"));
+        assert!(out.contains("lens: &'static [\n    ],"));
+        assert!(out.contains("len_idxs: &'static [\n        0,\n    ],"));
+        assert!(out.contains("len_buckets: &'static [\n        (3, 1),\n    \
+                              ],"));
+    }
+
+    #[test]
+    fn test_longest_prefix() {
+        let mut w = MemWriter::new();
+        PhfSetBuilder::new().longest_prefix().entry("foo").build(&mut w)
+                            .unwrap();
+        let out = str::from_utf8(w.get_ref()).unwrap();
+        assert!(out.contains("lens: &'static [\n        3,\n    ],"));
+    }
+
+    #[test]
+    fn test_escape() {
+        let mut w = MemWriter::new();
+        PhfSetBuilder::new().entry("\"\n").build(&mut w).unwrap();
+        let out = str::from_utf8(w.get_ref()).unwrap();
+        assert!(out.contains("(\"\\\"\\n\", 0),"));
+    }
+
+    #[test]
+    fn test_ordered_set() {
+        let mut w = MemWriter::new();
+        PhfOrderedSetBuilder::new().entry("foo").build(&mut w).unwrap();
+        let out = str::from_utf8(w.get_ref()).unwrap();
+        assert!(out.starts_with("::phf::PhfOrderedSet { map: \
+                                 ::phf::PhfOrderedMap {"));
+        assert!(out.contains("idxs: &'static [\n        0,\n    ],"));
+    }
+
+    #[test]
+    #[should_fail]
+    fn test_duplicate() {
+        let mut w = MemWriter::new();
+        let _ = PhfSetBuilder::new().entry("foo").entry("foo").build(&mut w);
+    }
 }